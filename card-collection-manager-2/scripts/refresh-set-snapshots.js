@@ -0,0 +1,56 @@
+// Refresh the set snapshots at `src-tauri/snapshots`, which are bundled with the app as fallback
+// for a first start without internet connection. Run via `yarn refresh-set-snapshots` (Node.js 18+)
+// and commit the updated snapshots.
+
+const fs = require("fs");
+const path = require("path");
+
+// number of sets per page when paging through the Pokemon TCG API
+const POKEMON_PAGE_SIZE = 250;
+
+const SNAPSHOT_DIR = path.join(__dirname, "..", "src-tauri", "snapshots");
+
+const download = async (url) => {
+  const response = await fetch(url, { headers: { "User-Agent": "card-collection-manager-2" } });
+  if (!response.ok) {
+    throw new Error(`Unable to download ${url}: ${response.status} ${response.statusText}`);
+  }
+  return response.json();
+};
+
+const downloadMagicSets = async () => {
+  const response = await download("https://api.scryfall.com/sets");
+  return { data: response.data };
+};
+
+// the Pokemon TCG API returns its sets in pages, which are combined into a single response
+const downloadPokemonSets = async () => {
+  let sets = [];
+  for (let page = 1; ; page++) {
+    const response = await download(`https://api.pokemontcg.io/v2/sets?page=${page}&pageSize=${POKEMON_PAGE_SIZE}`);
+    sets = sets.concat(response.data);
+    if (response.data.length == 0 || sets.length >= response.totalCount) {
+      break;
+    }
+  }
+  return { data: sets, totalCount: sets.length };
+};
+
+const writeSnapshot = (filename, snapshot) => {
+  // an empty snapshot would replace the sets of the app, so the previous snapshot is kept instead
+  if (snapshot.data.length == 0) {
+    throw new Error(`Received no sets for ${filename}.`);
+  }
+  fs.writeFileSync(path.join(SNAPSHOT_DIR, filename), JSON.stringify(snapshot) + "\n");
+  console.log(`Stored ${snapshot.data.length} sets to ${filename}.`);
+};
+
+const main = async () => {
+  writeSnapshot("magic_sets.json", await downloadMagicSets());
+  writeSnapshot("pokemon_sets.json", await downloadPokemonSets());
+};
+
+main().catch((error) => {
+  console.error(error.message);
+  process.exit(1);
+});
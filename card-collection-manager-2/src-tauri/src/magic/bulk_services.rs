@@ -0,0 +1,347 @@
+use std::io::{BufReader, Read, Write};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::fs::{File, create_dir_all, remove_dir_all, remove_file, rename};
+use std::time::UNIX_EPOCH;
+use serde::{Serialize, Deserialize};
+
+use crate::templates::catalog_service_templates::{CatalogCard, store_catalog};
+use crate::util::config::{Configuration, load_configuration};
+use crate::util::http;
+use crate::util::jobs::Job;
+
+/// Bulk data file of Scryfall that is imported, containing every card object on Scryfall
+/// in English or the only language it was printed in.
+const BULK_DATA_TYPE: &str = "default-cards";
+
+/// Prices of a MTG card in USD, EUR and MTGO event tickets, as provided by Scryfall.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Prices {
+    pub usd: Option<String>,
+    #[serde(rename = "usdFoil", alias = "usd_foil")]
+    pub usd_foil: Option<String>,
+    #[serde(rename = "usdEtched", alias = "usd_etched")]
+    pub usd_etched: Option<String>,
+    pub eur: Option<String>,
+    #[serde(rename = "eurFoil", alias = "eur_foil")]
+    pub eur_foil: Option<String>,
+    pub tix: Option<String>
+}
+
+/// MTG card of the imported Scryfall bulk data. Fields of the Scryfall card objects are read via
+/// their snake case aliases.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BulkCard {
+    /// card id according to Scryfall
+    pub id: String,
+    /// id of the card's oracle identity, which is shared by all printings of the card
+    #[serde(rename = "oracleId", alias = "oracle_id")]
+    pub oracle_id: Option<String>,
+    pub name: String,
+    /// code of the set the card was printed in
+    pub set: String,
+    #[serde(rename = "collectorNumber", alias = "collector_number")]
+    pub collector_number: String,
+    pub rarity: Option<String>,
+    pub lang: Option<String>,
+    #[serde(rename = "releasedAt", alias = "released_at")]
+    pub released_at: Option<String>,
+    #[serde(default)]
+    pub prices: Prices
+}
+
+/// Index of the imported Scryfall bulk data, stored at `<storage_location>/magic/bulk/index.json`.
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct BulkIndex {
+    /// set code of each card by card id
+    sets: HashMap<String, String>,
+    /// ids of all printings by oracle id
+    printings: HashMap<String, Vec<String>>
+}
+
+/// Metadata of the last bulk data import, stored at `<storage_location>/magic/bulk/meta.json`.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct BulkMetadata {
+    /// timestamp of the imported bulk file, either as provided by Scryfall or the
+    /// modification time of the local file in seconds since the unix epoch
+    #[serde(rename = "updatedAt")]
+    pub updated_at: String,
+    /// URL or path of the imported bulk file
+    pub source: String,
+    /// number of imported cards
+    pub cards: usize,
+    /// number of sets of the imported cards
+    pub sets: usize
+}
+
+/// Result of a bulk data import.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BulkImportReport {
+    /// flag to indicate whether the bulk data was imported or was already up-to-date
+    pub updated: bool,
+    #[serde(flatten)]
+    pub metadata: BulkMetadata
+}
+
+/// Import the Scryfall bulk data file of all default cards and store it in an indexed form at
+/// `<storage_location>/magic/bulk`. Additionally, the card catalogs of all sets are replaced with
+/// the imported cards, so name search and collector number lookup work offline. The import is
+/// skipped, if the timestamp of the bulk file did not change since the last import.
+///
+/// # Arguments
+/// `path`  - Path of a local bulk file. If not provided, the current bulk file is downloaded from Scryfall.
+/// `job`   - Job that runs the import.
+///
+/// # Returns
+/// Import report as JSON
+///
+pub fn import_bulk_data_with_job<'a>(path: Option<&str>, job: &Job) -> Result<String, &'a str> {
+    #[derive(Serialize, Deserialize, Debug)]
+    struct TmpBulkData {
+        pub updated_at: String,
+        pub download_uri: String
+    }
+
+    let config: Configuration = load_configuration().unwrap();
+    let bulk_dir = format!("{}/magic/bulk", &config.data_storage);
+    create_dir_all(&bulk_dir).unwrap();
+
+    let (source, updated_at) = match path {
+        Some(path) => (path.to_string(), get_modification_time(Path::new(path))?),
+        None => {
+            let bulk_data: TmpBulkData = http::get_json::<TmpBulkData>(&format!("{}/bulk-data/{}", &config.magic.api_url, BULK_DATA_TYPE), &[], &[])?;
+            (bulk_data.download_uri, bulk_data.updated_at)
+        }
+    };
+
+    let previous: BulkMetadata = load_bulk_metadata();
+    if previous.updated_at == updated_at {
+        return get_report_json(false, previous);
+    }
+
+    let bulk_file: PathBuf = match path {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let target = Path::new(&bulk_dir).join(format!("{}.json", BULK_DATA_TYPE));
+            let downloaded = http::download(&source, &target, job);
+            if downloaded.is_err() {
+                let _ = remove_file(&target);
+            }
+            downloaded?;
+            target
+        }
+    };
+
+    job.report_progress(0.0, "Reading bulk data");
+    let cards: Result<Vec<BulkCard>, _> = match File::open(&bulk_file) {
+        Ok(f) => serde_json::from_reader(BufReader::new(f)),
+        Err(_) => return Err("Unable to open bulk data file.")
+    };
+    if path.is_none() {
+        let _ = remove_file(&bulk_file);
+    }
+    let cards: Vec<BulkCard> = match cards {
+        Ok(cards) => cards,
+        Err(_) => return Err("Unable to deserialize bulk data file.")
+    };
+    job.check_cancelled()?;
+
+    let metadata = store_bulk_cards(&bulk_dir, source, updated_at, cards, job)?;
+    get_report_json(true, metadata)
+}
+
+/// Get the metadata of the last bulk data import as JSON.
+pub fn get_bulk_metadata_json<'a>() -> Result<String, &'a str> {
+    match serde_json::to_string(&load_bulk_metadata()) {
+        Ok(json) => Ok(json),
+        Err(_) => Err("Unable to serialize bulk data metadata to JSON.")
+    }
+}
+
+/// Get the card with the provided Scryfall id from the imported bulk data.
+///
+/// # Argument
+/// `id`    - Scryfall id of the card.
+///
+pub fn get_bulk_card(id: &str) -> Option<BulkCard> {
+    let index: BulkIndex = load_bulk_index();
+    let set = index.sets.get(id)?;
+    load_bulk_set(set).into_iter().find(|card| card.id == id)
+}
+
+/// Get all printings of the card with the provided Scryfall id from the imported bulk data,
+/// i.e. all cards with the same oracle id, as JSON list sorted by release date.
+///
+/// # Argument
+/// `id`    - Scryfall id of the card.
+///
+pub fn get_printings_json<'a>(id: &str) -> Result<String, &'a str> {
+    let index: BulkIndex = load_bulk_index();
+    let oracle_id: Option<String> = index.sets.get(id)
+        .and_then(|set| load_bulk_set(set).into_iter().find(|card| card.id == id))
+        .and_then(|card| card.oracle_id);
+    let ids: Vec<String> = match oracle_id.and_then(|oracle_id| index.printings.get(&oracle_id)) {
+        Some(ids) => ids.clone(),
+        None => return Err("Unable to find card within bulk data.")
+    };
+
+    // group the printings by set to load each set file only once
+    let mut sets: HashMap<&str, Vec<&str>> = HashMap::new();
+    for id in ids.iter() {
+        if let Some(set) = index.sets.get(id) {
+            sets.entry(set).or_default().push(id);
+        }
+    }
+    let mut printings: Vec<BulkCard> = Vec::new();
+    for (set, ids) in sets.into_iter() {
+        printings.extend(load_bulk_set(set).into_iter().filter(|card| ids.contains(&card.id.as_str())));
+    }
+    printings.sort_by(|a, b| a.released_at.cmp(&b.released_at).then_with(|| a.set.cmp(&b.set)));
+
+    match serde_json::to_string(&printings) {
+        Ok(json) => Ok(json),
+        Err(_) => Err("Unable to serialize printings to JSON.")
+    }
+}
+
+/// Get the prices of the card with the provided Scryfall id from the imported bulk data as JSON.
+///
+/// # Argument
+/// `id`    - Scryfall id of the card.
+///
+pub fn get_prices_json<'a>(id: &str) -> Result<String, &'a str> {
+    match get_bulk_card(id) {
+        Some(card) => match serde_json::to_string(&card.prices) {
+            Ok(json) => Ok(json),
+            Err(_) => Err("Unable to serialize prices to JSON.")
+        },
+        None => Err("Unable to find card within bulk data.")
+    }
+}
+
+/// Store the provided cards set by set at `<storage_location>/magic/bulk/sets/<set>.json`, together
+/// with an index of all cards and printings, and replace the card catalogs of all sets. The bulk data is
+/// written to a temporary sibling directory first, which replaces the previous bulk data once it is complete,
+/// so a cancelled import keeps the previous bulk data.
+fn store_bulk_cards<'a>(bulk_dir: &str, source: String, updated_at: String, cards: Vec<BulkCard>, job: &Job) -> Result<BulkMetadata, &'a str> {
+    let card_count = cards.len();
+    let mut index = BulkIndex::default();
+    let mut sets: HashMap<String, Vec<BulkCard>> = HashMap::new();
+    for card in cards.into_iter() {
+        index.sets.insert(card.id.clone(), card.set.clone());
+        if let Some(oracle_id) = &card.oracle_id {
+            index.printings.entry(oracle_id.clone()).or_default().push(card.id.clone());
+        }
+        sets.entry(card.set.clone()).or_default().push(card);
+    }
+
+    let tmp_dir = format!("{}.tmp", bulk_dir);
+    if Path::new(&tmp_dir).exists() {
+        remove_dir_all(&tmp_dir).expect("Unable to remove incomplete bulk data.");
+    }
+    create_dir_all(format!("{}/sets", tmp_dir)).unwrap();
+
+    let set_count = sets.len();
+    let metadata = BulkMetadata { updated_at: updated_at, source: source, cards: card_count, sets: set_count };
+    let stored: Result<(), &str> = write_bulk_dir(&tmp_dir, &sets, &index, &metadata, job);
+    if stored.is_err() {
+        let _ = remove_dir_all(&tmp_dir);
+        stored?;
+    }
+
+    // swap the complete bulk data in
+    let old_dir = format!("{}.old", bulk_dir);
+    if Path::new(&old_dir).exists() {
+        remove_dir_all(&old_dir).expect("Unable to remove outdated bulk data.");
+    }
+    if rename(bulk_dir, &old_dir).is_err() || rename(&tmp_dir, bulk_dir).is_err() {
+        let _ = rename(&old_dir, bulk_dir);
+        let _ = remove_dir_all(&tmp_dir);
+        return Err("Unable to replace bulk data.");
+    }
+    let _ = remove_dir_all(&old_dir);
+
+    for (set, cards) in sets.iter() {
+        let catalog: Vec<CatalogCard> = cards
+            .iter()
+            .map(|card| CatalogCard {
+                id: card.id.clone(),
+                name: card.name.clone(),
+                number: card.collector_number.clone(),
+                rarity: card.rarity.clone()
+            })
+            .collect();
+        store_catalog("magic", set, &catalog)?;
+    }
+    Ok(metadata)
+}
+
+/// Write the provided cards set by set, the index and the metadata of the bulk data to the provided directory.
+fn write_bulk_dir<'a>(dir: &str, sets: &HashMap<String, Vec<BulkCard>>, index: &BulkIndex, metadata: &BulkMetadata, job: &Job) -> Result<(), &'a str> {
+    for (i, (set, cards)) in sets.iter().enumerate() {
+        job.check_cancelled()?;
+        job.report_progress(i as f32 / sets.len() as f32, "Storing bulk data");
+        write_json_file(&format!("{}/sets/{}", dir, get_bulk_set_filename(set)), cards)?;
+    }
+    write_json_file(&format!("{}/index.json", dir), index)?;
+    write_json_file(&format!("{}/meta.json", dir), metadata)?;
+    job.check_cancelled()
+}
+
+fn load_bulk_metadata() -> BulkMetadata {
+    let config: Configuration = load_configuration().unwrap();
+    read_json_file(&format!("{}/magic/bulk/meta.json", &config.data_storage)).unwrap_or_default()
+}
+
+fn load_bulk_index() -> BulkIndex {
+    let config: Configuration = load_configuration().unwrap();
+    read_json_file(&format!("{}/magic/bulk/index.json", &config.data_storage)).unwrap_or_default()
+}
+
+fn load_bulk_set(set: &str) -> Vec<BulkCard> {
+    read_json_file(&get_bulk_set_file(set)).unwrap_or_default()
+}
+
+fn get_bulk_set_file(set: &str) -> String {
+    let config: Configuration = load_configuration().unwrap();
+    format!("{}/magic/bulk/sets/{}", &config.data_storage, get_bulk_set_filename(set))
+}
+
+fn get_bulk_set_filename(set: &str) -> String {
+    let filename: String = set.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' }).collect();
+    format!("{}.json", filename)
+}
+
+fn read_json_file<T: serde::de::DeserializeOwned>(path: &str) -> Option<T> {
+    let mut data = String::new();
+    let mut f = File::open(path).ok()?;
+    f.read_to_string(&mut data).expect("Unable to read file to string.");
+    serde_json::from_str(&data).ok()
+}
+
+fn write_json_file<'a, T: Serialize>(path: &str, data: &T) -> Result<(), &'a str> {
+    let json = serde_json::to_string(data).expect("Unable to serialize bulk data to JSON.");
+    let mut f = match File::create(path) {
+        Ok(f) => f,
+        Err(_) => return Err("Unable to create bulk data file.")
+    };
+    match f.write_all(json.as_bytes()) {
+        Ok(_) => Ok(()),
+        Err(_) => Err("Unable to write bulk data file.")
+    }
+}
+
+/// Get the modification time of the provided file in seconds since the unix epoch.
+fn get_modification_time<'a>(path: &Path) -> Result<String, &'a str> {
+    match path.metadata().and_then(|metadata| metadata.modified()) {
+        Ok(modified) => Ok(modified.duration_since(UNIX_EPOCH).unwrap().as_secs().to_string()),
+        Err(_) => Err("Unable to read bulk data file.")
+    }
+}
+
+fn get_report_json<'a>(updated: bool, metadata: BulkMetadata) -> Result<String, &'a str> {
+    match serde_json::to_string(&BulkImportReport { updated: updated, metadata: metadata }) {
+        Ok(json) => Ok(json),
+        Err(_) => Err("Unable to serialize bulk data import report to JSON.")
+    }
+}
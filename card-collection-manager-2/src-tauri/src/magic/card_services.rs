@@ -1,12 +1,17 @@
+use std::collections::HashMap;
 use serde::{Serialize, Deserialize};
 
-use crate::util::enums::{Language, Condition};
+use crate::util::enums::{Language, Condition, ImageRole};
 use crate::util::collection::MapEntryWithId;
-use crate::util::fs::parse_index_from_filename;
 
 use super::set_services::Set;
 
 use crate::templates;
+use crate::templates::card_service_templates::{EntryWithSet, EntryWithIdentity, EntryWithImages};
+use crate::templates::image_templates::ImageEdit;
+use crate::util::jobs::Job;
+
+use super::catalog_services::fetch_catalog;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Card {
@@ -14,8 +19,16 @@ pub struct Card {
     pub amount: u8,
     pub name: String,
     pub set: Set,
+    #[serde(rename = "setNo", default)]
+    pub set_no: String,
+    #[serde(rename = "upstreamId", default)]
+    pub upstream_id: Option<String>,
     pub note: String,
     pub images: Vec<String>,
+    #[serde(rename = "imageRoles", default)]
+    pub image_roles: HashMap<String, ImageRole>,
+    #[serde(rename = "primaryImage", default)]
+    pub primary_image: Option<String>,
     pub language: Language,
     pub condition: Condition,
     pub foil: bool,
@@ -34,6 +47,61 @@ impl MapEntryWithId for Card {
     }
 }
 
+/// implementation of the Card struct that is used by templates
+/// to store the card's set as a reference by id.
+impl EntryWithSet for Card {
+    type Set = Set;
+}
+
+/// implementation of the Card struct that is used by templates
+/// to link the card to its card identity within the game's API.
+impl EntryWithIdentity for Card {
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+    fn get_set_id(&self) -> &str {
+        &self.set.id
+    }
+    fn get_set_no(&self) -> &str {
+        &self.set_no
+    }
+    fn get_upstream_id(&self) -> Option<&str> {
+        self.upstream_id.as_deref()
+    }
+    fn set_upstream_id(&mut self, upstream_id: Option<String>) {
+        self.upstream_id = upstream_id;
+    }
+}
+
+/// implementation of the Card struct that is used by templates
+/// to name its images according to the configured filename template.
+impl EntryWithImages for Card {
+    fn get_set(&self) -> &Set {
+        &self.set
+    }
+    fn get_language(&self) -> &Language {
+        &self.language
+    }
+    fn get_images(&self) -> &Vec<String> {
+        &self.images
+    }
+    fn set_images(&mut self, images: Vec<String>) {
+        self.images = images;
+    }
+    fn get_image_roles(&self) -> &HashMap<String, ImageRole> {
+        &self.image_roles
+    }
+    fn set_image_roles(&mut self, image_roles: HashMap<String, ImageRole>) {
+        self.image_roles = image_roles;
+    }
+    fn get_primary_image(&self) -> Option<&str> {
+        self.primary_image.as_deref()
+    }
+    fn set_primary_image(&mut self, primary_image: Option<String>) {
+        self.primary_image = primary_image;
+    }
+}
+
 /// Parse the provided JSON object into an instance of `Card`, store it into 
 /// the Magic collection hashmap and return the id of the new entry.
 /// 
@@ -54,8 +122,12 @@ pub fn add_card<'a>(obj: &str) -> Result<u32, &'a str> {
 /// # Argument
 /// obj     - Pokemon card instance as JSON object.
 /// 
-pub fn update_card<'a>(obj: &str) -> Result<(), &'a str> {
-    templates::card_service_templates::update_entry_in_collection::<Card>("magic", obj)
+/// # Returns
+/// The updated card as JSON object, whose images are renamed according to the filename template
+/// 
+pub fn update_card<'a>(obj: &str) -> Result<String, &'a str> {
+    let card: Card = templates::card_service_templates::update_entry_in_collection::<Card>("magic", obj)?;
+    get_card_json(&card)
 }
 
 /// Delete the entry within the Magic collection hashmap with the provided id.
@@ -66,7 +138,7 @@ pub fn update_card<'a>(obj: &str) -> Result<(), &'a str> {
 pub fn delete_card<'a>(id: &u32) -> Result<(), &'a str> {
     let card: Card = templates::card_service_templates::get_entry_by_id::<Card>("magic", id).unwrap();
     for image in card.images.iter() {
-        delete_image(image);
+        delete_image(image)?;
     }
     templates::card_service_templates::delete_entry_by_id::<Card>("magic", id)
 }
@@ -77,6 +149,90 @@ pub fn get_collection_json<'a>() -> Result<String, &'a str> {
     templates::card_service_templates::get_collection_json::<Card>("magic")
 }
 
+/// Link all entries of the Magic collection without card identity to their card within the
+/// catalog of their set, based on name and collector number, and return a report of all
+/// entries that could not be linked unambiguously as JSON.
+/// 
+/// # Argument
+/// job     - Job that runs the resolution.
+/// 
+pub fn resolve_identities_with_job<'a>(job: &Job) -> Result<String, &'a str> {
+    let report = templates::card_service_templates::resolve_identities::<Card>("magic", fetch_catalog, job)?;
+    match serde_json::to_string(&report) {
+        Ok(json) => Ok(json),
+        Err(_) => Err("Unable to serialize identity report to JSON.")
+    }
+}
+
+/// Rename the images of all entries of the Magic collection according to the configured filename
+/// template and return a report of the renamed images as JSON.
+/// 
+/// # Argument
+/// job     - Job that runs the renaming.
+/// 
+pub fn rename_images_with_job<'a>(job: &Job) -> Result<String, &'a str> {
+    let report = templates::card_service_templates::rename_images::<Card>("magic", job)?;
+    match serde_json::to_string(&report) {
+        Ok(json) => Ok(json),
+        Err(_) => Err("Unable to serialize image rename report to JSON.")
+    }
+}
+
+/// Merge the images of the Magic collection that have the same content and return a report of
+/// the storage saved by sharing them as JSON.
+/// 
+/// # Argument
+/// job     - Job that runs the deduplication.
+/// 
+pub fn deduplicate_images_with_job<'a>(job: &Job) -> Result<String, &'a str> {
+    let report = templates::card_service_templates::deduplicate_images::<Card>("magic", job)?;
+    match serde_json::to_string(&report) {
+        Ok(json) => Ok(json),
+        Err(_) => Err("Unable to serialize deduplication report to JSON.")
+    }
+}
+
+/// Check the images of the Magic collection against the Magic image directory and return
+/// a report of orphaned images and dangling image references as JSON.
+/// 
+pub fn check_images_json<'a>() -> Result<String, &'a str> {
+    let report = templates::card_service_templates::check_images::<Card>("magic")?;
+    match serde_json::to_string(&report) {
+        Ok(json) => Ok(json),
+        Err(_) => Err("Unable to serialize image check report to JSON.")
+    }
+}
+
+/// Repair the images of the Magic collection and return a report of the remaining orphaned
+/// images and dangling image references as JSON.
+/// 
+/// # Argument
+/// attach          - Orphaned images that should be attached to the cards their names suggest.
+/// delete          - Orphaned images that should be deleted.
+/// remove_dangling - Indicator whether references to missing images should be removed from the cards.
+/// 
+pub fn repair_images_json<'a>(attach: &Vec<String>, delete: &Vec<String>, remove_dangling: bool) -> Result<String, &'a str> {
+    let report = templates::card_service_templates::repair_images::<Card>("magic", attach, delete, remove_dangling)?;
+    match serde_json::to_string(&report) {
+        Ok(json) => Ok(json),
+        Err(_) => Err("Unable to serialize image check report to JSON.")
+    }
+}
+
+/// Convert all entries of the Magic collection that still embed a full copy of
+/// their set into entries that reference their set by id.
+/// 
+pub fn migrate_set_references<'a>() -> Result<(), &'a str> {
+    templates::card_service_templates::migrate_set_references::<Card>("magic")
+}
+
+/// Get a report of all entries of the Magic collection whose set id cannot be 
+/// found within the available Magic sets, as JSON encoded string.
+/// 
+pub fn get_set_reference_report_json<'a>() -> Result<String, &'a str> {
+    templates::card_service_templates::get_set_reference_report_json::<Card>("magic")
+}
+
 /// Copy the image specfied by the image location parameter as an image that is 
 /// related to the provided Card object to the collection's image directory. 
 /// The flag `new_entry` indicates if the provided Card object is an existing object
@@ -95,25 +251,53 @@ pub fn get_collection_json<'a>() -> Result<String, &'a str> {
 pub fn copy_image<'a>(obj: &str, img_location: &str, new_entry: bool) -> Result<String, &'a str> {
     let card: Card = serde_json::from_str(obj).expect("Unable to deserialize card object.");
 
-    // get next image index
-    let mut index: u8 = 0;
-    if card.images.len() > 0 {
-        // support of file names of card collection manager v1. In this case, we start with index 0.
-        let last_element = card.images.get(card.images.len() - 1).unwrap();
-        if !(last_element.contains("IMG_FRONT") || last_element.contains("IMG_BACK")) {
-            index = parse_index_from_filename(last_element.as_str()) + 1;
-        }
-    }
+    // the template function allocates the next free image index, which supports the image names of card collection manager v1,
+    // builds the image name and requests the next id automatically, if its a new entry
+    templates::card_service_templates::copy_image::<Card>(img_location, &card, "magic", new_entry)
+}
 
-    // image name, if its a new entry, the template function will request the next id automatically
-    let img_target_name: String = match new_entry {
-        true => format!("{}+{}+{}", card.set.name, &card.name, index),
-        false => format!("{}+{}+{}+{}", card.id, card.set.name, &card.name, index)
-    };
+/// Move the images of the card with the provided id into the provided order and return
+/// the updated card as JSON object.
+///
+/// # Arguments
+/// id          - Id of the card within the collection.
+/// images      - All images of the card in their new order.
+///
+pub fn reorder_images<'a>(id: &u32, images: Vec<String>) -> Result<String, &'a str> {
+    let card: Card = templates::card_service_templates::reorder_entry_images::<Card>("magic", id, images)?;
+    get_card_json(&card)
+}
+
+/// Assign the provided role to an image of the card with the provided id and return
+/// the updated card as JSON object. If no role is provided, the role is removed.
+///
+/// # Arguments
+/// id          - Id of the card within the collection.
+/// image       - Name of the image.
+/// role        - New role of the image.
+///
+pub fn set_image_role<'a>(id: &u32, image: &str, role: Option<ImageRole>) -> Result<String, &'a str> {
+    let card: Card = templates::card_service_templates::set_entry_image_role::<Card>("magic", id, image, role)?;
+    get_card_json(&card)
+}
 
-    // todo- fallback support old card image names from card collection manager 1
+/// Designate an image of the card with the provided id as its primary image and return
+/// the updated card as JSON object.
+///
+/// # Arguments
+/// id          - Id of the card within the collection.
+/// image       - Name of the primary image or `None` to remove the designation.
+///
+pub fn set_primary_image<'a>(id: &u32, image: Option<&str>) -> Result<String, &'a str> {
+    let card: Card = templates::card_service_templates::set_entry_primary_image::<Card>("magic", id, image)?;
+    get_card_json(&card)
+}
 
-    templates::card_service_templates::copy_image::<Card>(img_location, &img_target_name, "magic", new_entry)
+fn get_card_json<'a>(card: &Card) -> Result<String, &'a str> {
+    match serde_json::to_string(card) {
+        Ok(json) => Ok(json),
+        Err(_) => Err("Unable to serialize card to JSON.")
+    }
 }
 
 /// Delete the image with the specified name from the card collection directory.
@@ -125,15 +309,44 @@ pub fn delete_image<'a>(image: &str) -> Result<(), &'a str> {
     templates::card_service_templates::delete_image("magic", image)
 }
 
-/// Get the image with the specified name from the card collection directory 
-/// as base-64 encoded string.
-/// 
+/// Delete the image with the specified name, that was copied for a card but not saved with it,
+/// e.g. since creating or editing the card was cancelled. Images of saved cards are kept.
+///
 /// # Arguments
-/// image       - Name of image that should be returned.
-/// 
-/// # Returns
-/// Image as base-64 encoded string
-/// 
-pub fn get_image_b64<'a>(image: &str) -> Result<String, &'a str> {
-    templates::card_service_templates::get_entry_image_b64("magic", image)
+/// image       - Name of image that should be discarded.
+///
+pub fn discard_image<'a>(image: &str) -> Result<(), &'a str> {
+    templates::card_service_templates::discard_image("magic", image)
+}
+
+/// Apply the provided editing operation to the image with the specified name of the card with the
+/// specified id and return the name of the edited image and the number of edits that can be undone as JSON.
+/// Images that are shared with other cards are copied first.
+///
+/// # Arguments
+/// id          - Id of the card within the collection, whose image should be edited.
+/// image       - Name of image that should be edited.
+/// edit        - Operation that should be applied to the image.
+///
+pub fn edit_image<'a>(id: &u32, image: &str, edit: &ImageEdit) -> Result<String, &'a str> {
+    let result = templates::card_service_templates::edit_entry_image::<Card>("magic", id, image, edit)?;
+    match serde_json::to_string(&result) {
+        Ok(json) => Ok(json),
+        Err(_) => Err("Unable to serialize image edit result to JSON.")
+    }
+}
+
+/// Undo the last editing operation of the image with the specified name of the card with the specified
+/// id and return the name of the restored image and the number of edits that can still be undone as JSON.
+///
+/// # Arguments
+/// id          - Id of the card within the collection, whose image edit should be undone.
+/// image       - Name of image whose last edit should be undone.
+///
+pub fn undo_image_edit<'a>(id: &u32, image: &str) -> Result<String, &'a str> {
+    let result = templates::card_service_templates::undo_entry_image_edit::<Card>("magic", id, image)?;
+    match serde_json::to_string(&result) {
+        Ok(json) => Ok(json),
+        Err(_) => Err("Unable to serialize image edit result to JSON.")
+    }
 }
\ No newline at end of file
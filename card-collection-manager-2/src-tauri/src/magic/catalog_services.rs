@@ -0,0 +1,126 @@
+use serde::{Deserialize, Serialize};
+
+use crate::templates;
+use crate::templates::catalog_service_templates::CatalogCard;
+use crate::util::config::{Configuration, load_configuration};
+use crate::util::http;
+use crate::util::jobs::Job;
+
+use super::set_services::Set;
+
+/// Get the card catalog of the MTG set with the provided id as JSON list. The catalog is cached at
+/// `<storage_location>/magic/catalog/<set>.json` and only fetched, if it is not cached yet.
+///
+/// # Argument
+/// `set_id`    - Id of the set whose catalog should be returned.
+///
+pub fn get_catalog_json<'a>(set_id: &str) -> Result<String, &'a str> {
+    let catalog = templates::catalog_service_templates::get_catalog("magic", set_id, fetch_catalog, &Job::detached())?;
+    match serde_json::to_string(&catalog) {
+        Ok(json) => Ok(json),
+        Err(_) => Err("Unable to serialize catalog to JSON.")
+    }
+}
+
+/// Fetch the card catalog of the MTG set with the provided id, replace the cached catalog of the
+/// set and return it as JSON list.
+///
+/// # Arguments
+/// `set_id`    - Id of the set whose catalog should be updated.
+/// `job`       - Job that runs the catalog update.
+///
+pub fn update_catalog_with_job<'a>(set_id: &str, job: &Job) -> Result<String, &'a str> {
+    let catalog = templates::catalog_service_templates::update_catalog("magic", set_id, fetch_catalog, job)?;
+    match serde_json::to_string(&catalog) {
+        Ok(json) => Ok(json),
+        Err(_) => Err("Unable to serialize catalog to JSON.")
+    }
+}
+
+/// Search the cached MTG card catalogs for cards whose name matches the provided query and return
+/// the matches as JSON list, best matches first. The search ignores case and accents and tolerates typos.
+///
+/// # Arguments
+/// `query`     - (Partial) card name to search for.
+/// `set_id`    - Id of the set whose catalog should be searched. If not provided, all cached catalogs are searched.
+/// `limit`     - Maximum number of returned matches.
+///
+pub fn search_catalog_json<'a>(query: &str, set_id: Option<&str>, limit: usize) -> Result<String, &'a str> {
+    let matches = templates::catalog_service_templates::search_catalog::<Set>("magic", query, set_id, limit, fetch_catalog, &Job::detached())?;
+    match serde_json::to_string(&matches) {
+        Ok(json) => Ok(json),
+        Err(_) => Err("Unable to serialize catalog matches to JSON.")
+    }
+}
+
+/// Look up the MTG card with the provided collector number within the set with the provided id
+/// or code, e.g. `dom` and `4`, and return it as JSON object. If there is no such card, `null` is returned.
+///
+/// # Arguments
+/// `set`       - Id or code of the set.
+/// `number`    - Collector number of the card within the set.
+///
+pub fn lookup_card_json<'a>(set: &str, number: &str) -> Result<String, &'a str> {
+    let card = templates::catalog_service_templates::lookup_card::<Set>("magic", set, number, fetch_catalog, &Job::detached())?;
+    match serde_json::to_string(&card) {
+        Ok(json) => Ok(json),
+        Err(_) => Err("Unable to serialize card to JSON.")
+    }
+}
+
+/// Fetch all cards of the MTG set with the provided id.
+///
+/// This function call the REST-API at `https://api.scryfall.com` (or the API URL from the
+/// configuration) to retrieve the data. The cards are fetched page by page until all cards
+/// are retrieved.
+///
+pub fn fetch_catalog<'a>(set_id: &str, job: &Job) -> Result<Vec<CatalogCard>, &'a str> {
+    #[derive(Serialize, Deserialize, Debug)]
+    struct TmpCard {
+        pub id: String,
+        pub name: String,
+        pub collector_number: String,
+        pub rarity: Option<String>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    struct Response {
+        data: Vec<TmpCard>,
+        has_more: bool,
+        next_page: Option<String>,
+        total_cards: Option<usize>,
+    }
+
+    let config: Configuration = load_configuration().unwrap();
+    let query = [
+        ("q", format!("set:{}", set_id)),
+        ("unique", String::from("prints")),
+        ("order", String::from("set")),
+        ("include_extras", String::from("true")),
+        ("include_variations", String::from("true")),
+    ];
+
+    job.report_progress(0.0, "Fetching Magic cards");
+    let mut resp: Response = http::get_json::<Response>(&format!("{}/cards/search", &config.magic.api_url), &query, &[])?;
+    let mut catalog: Vec<CatalogCard> = Vec::new();
+    loop {
+        catalog.extend(resp.data.into_iter().map(|card| CatalogCard {
+            id: card.id,
+            name: card.name,
+            number: card.collector_number,
+            rarity: card.rarity,
+        }));
+        if let Some(total_cards) = resp.total_cards {
+            job.report_progress(catalog.len() as f32 / total_cards.max(1) as f32, "Fetching Magic cards");
+        }
+
+        // the response provides the complete url of the next page
+        let next_page = match (resp.has_more, resp.next_page) {
+            (true, Some(next_page)) => next_page,
+            _ => break,
+        };
+        job.check_cancelled()?;
+        resp = http::get_json::<Response>(&next_page, &[], &[])?;
+    }
+    Ok(catalog)
+}
@@ -1,2 +1,4 @@
 pub mod card_services;
-pub mod set_services;
\ No newline at end of file
+pub mod set_services;
+pub mod catalog_services;
+pub mod bulk_services;
\ No newline at end of file
@@ -1,70 +1,199 @@
+use std::sync::atomic::AtomicBool;
 use crate::templates;
+use crate::templates::set_service_templates::{SetEntry, SetFetchMetadata};
+use crate::util::enums::SetAsset;
+use crate::util::config::{Configuration, load_configuration};
+use crate::util::jobs::Job;
 use serde::{Deserialize, Serialize};
 
+/// flag that indicates whether a background update of the sets is running
+static BACKGROUND_UPDATE: AtomicBool = AtomicBool::new(false);
+
+/// Magic set information as provided by the API
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct TmpSet {
+    pub code: String,
+    pub name: String,
+    pub released_at: String,
+    pub digital: bool,
+    pub set_type: String,
+    pub card_count: u32,
+    pub printed_size: Option<u32>,
+    pub block: Option<String>,
+    pub parent_set_code: Option<String>,
+    pub icon_svg_uri: Option<String>,
+}
+
+/// Response of the API for the list of sets
+#[derive(Serialize, Deserialize, Debug)]
+struct Response {
+    data: Vec<TmpSet>,
+}
+
 // Magic set information
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Set {
-    /// set id accoring to API at `https://api.magicthegathering.io`
+    /// set id accoring to API at `https://api.scryfall.com`
     pub id: String,
     /// actual set name
     pub name: String,
     /// release date in format YYYY/MM/DD
     #[serde(rename = "releaseDate")]
     pub release_date: String,
+    /// set type according to Scryfall, e.g. `core`, `expansion`, `promo` or `token`
+    #[serde(rename = "setType", default)]
+    pub set_type: Option<String>,
+    /// number of cards as printed on the cards, if known
+    #[serde(rename = "printedTotal", default)]
+    pub printed_total: Option<u32>,
+    /// total number of cards within the set
+    #[serde(default)]
+    pub total: Option<u32>,
+    /// block the set belongs to, e.g. `Ravnica`
+    #[serde(default)]
+    pub series: Option<String>,
+    /// id of the parent set, e.g. the main expansion of a token or promo set
+    #[serde(rename = "parentSet", default)]
+    pub parent_set: Option<String>,
+    /// URL of the set symbol image (SVG)
+    #[serde(rename = "symbolUrl", default)]
+    pub symbol_url: Option<String>,
+    /// flag to indicate a set that was only released digitally
+    #[serde(default)]
+    pub digital: bool,
+    /// flag to indicate a user-defined set that is not provided by the API
+    #[serde(default)]
+    pub custom: bool,
+}
+
+/// implementation of the Set struct that is used by templates
+/// to enable generic handling.
+impl SetEntry for Set {
+    fn get_id(&self) -> &str {
+        &self.id
+    }
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+    fn get_release_date(&self) -> &str {
+        &self.release_date
+    }
+    fn get_code(&self) -> Option<&str> {
+        None
+    }
+    fn set_custom(&mut self, custom: bool) {
+        self.custom = custom;
+    }
+    fn get_symbol_url(&self) -> Option<&str> {
+        self.symbol_url.as_deref()
+    }
+    fn get_logo_url(&self) -> Option<&str> {
+        None
+    }
+    fn is_selectable(&self, config: &Configuration) -> bool {
+        if self.custom {
+            return true;
+        }
+        if self.digital && !config.magic.digital_sets {
+            return false;
+        }
+        match &self.set_type {
+            Some(set_type) => config.magic.set_types.is_empty() || config.magic.set_types.contains(set_type),
+            None => true
+        }
+    }
 }
 
 /// Get all MTG sets as vector of `Set` instances.
 /// Implicitly, this function also stores the resulting data in JSON format
 /// at `<storage_location>/magic/sets.json`.
 ///
-/// This function call the REST-API at `https://api.magicthegathering.io` to retrieve the data.
+/// This function call the REST-API at `https://api.scryfall.com` (or the API URL from the
+/// configuration) to retrieve the data.
 ///
 pub fn update_sets<'a>() -> Result<Vec<Set>, &'a str> {
-    #[derive(Serialize, Deserialize, Debug, Clone)]
-    struct TmpSet {
-        pub code: String,
-        pub name: String,
-        pub released_at: String,
-        pub digital: bool,
-    }
+    update_sets_with_job(&Job::detached())
+}
 
-    #[derive(Serialize, Deserialize, Debug)]
-    struct Response {
-        data: Vec<TmpSet>,
-    }
+/// Same as `update_sets`, but reports its progress to the provided job and stops without
+/// storing any data, if the job gets cancelled.
+///
+/// # Argument
+/// `job`   - Job that runs the set update.
+///
+pub fn update_sets_with_job<'a>(job: &Job) -> Result<Vec<Set>, &'a str> {
+    let game = "magic";
+    let config: Configuration = load_configuration().unwrap();
+
+    // conditional request, if the sets have not been modified since the last fetch, we keep the local ones
+    job.report_progress(0.0, "Fetching Magic sets");
+    let url = format!("{}/sets", &config.magic.api_url);
+    let (resp, metadata) = match templates::set_service_templates::fetch_sets::<Response>(game, &url, &[], &[])? {
+        Some(fetched) => fetched,
+        None => {
+            job.report_progress(1.0, "Magic sets are up to date");
+            return templates::set_service_templates::load_fetched_sets::<Set>(game);
+        }
+    };
+    job.check_cancelled()?;
 
-    let resp = reqwest::blocking::get("https://api.scryfall.com/sets")
-        .unwrap()
-        .json::<Response>()
-        .unwrap();
+    job.report_progress(0.9, "Storing Magic sets");
+    let prepared_sets = prepare_sets(resp.data);
+    store_sets(&prepared_sets).unwrap();
+    templates::set_service_templates::store_fetch_metadata(game, &metadata)?;
+    job.report_progress(1.0, "Magic sets updated");
+
+    Ok(prepared_sets)
+}
 
-    // filter out 'onlyOnline', map to final struct and sort by release date
-    let mut prepared_sets = resp
-        .data
+/// Map the provided sets as provided by the API to instances of `Set`, sorted by release date.
+/// Digital sets and set types are not filtered out here but via the configuration when the sets 
+/// are requested.
+fn prepare_sets(sets: Vec<TmpSet>) -> Vec<Set> {
+    let mut prepared_sets = sets
         .iter()
-        .filter(|&set| !set.digital)
         .map(|set| Set {
             id: set.code.clone(),
             name: set.name.clone(),
             release_date: set.released_at.clone().replace("-", "/"),
+            set_type: Some(set.set_type.clone()),
+            printed_total: set.printed_size,
+            total: Some(set.card_count),
+            series: set.block.clone(),
+            parent_set: set.parent_set_code.clone(),
+            symbol_url: set.icon_svg_uri.clone(),
+            digital: set.digital,
+            custom: false,
         })
         .collect::<Vec<_>>();
     prepared_sets.sort_by_key(|set| set.release_date.clone());
-    store_sets(&prepared_sets).unwrap();
+    prepared_sets
+}
 
-    Ok(prepared_sets)
+/// Get the MTG sets from the snapshot at `src-tauri/snapshots`, that is embedded into the app.
+/// The snapshot is used on first start, if the API is unreachable.
+fn load_set_snapshot() -> Vec<Set> {
+    let snapshot: Response = serde_json::from_str(include_str!("../../snapshots/magic_sets.json"))
+        .expect("Unable to deserialize set snapshot.");
+    prepare_sets(snapshot.data)
 }
 
 /// Get all available MTG sets from either the locally stored file at `<storage_location>/magic/sets.json`
 /// or a fresh fetch from the corresponding API, in JSON format as a string.
 /// If the file does not exist, it will automatically fetch the data from the
 /// REST-API, store the result in the `sets.json` file and return the data as JSON.
+/// If the REST-API is unreachable in this case, the set snapshot bundled with the app is used.
+/// If the local file is older than the maximum age from the configuration, its data is returned
+/// and a refresh is started in the background.
+/// User-defined sets from `<storage_location>/magic/custom_sets.json` are always merged into
+/// the result, with their field `custom` set to `true`.
 /// 
 /// # Arguments
 /// `from_local`    - If `true`, the function will try to access the local `set.json` file and only
 ///                   fetch the API, if it cannot find this file. If `false`, it will fetch the API
 ///                   for set data, store it in the local `set.json` file and then provide its content
-///                   as a JSON string.
+///                   as a JSON string. The API is requested conditionally, so unchanged set data is
+///                   not downloaded again.
 /// 
 pub fn get_sets_json<'a>(from_local: bool) -> Result<String, &'a str> {
    
@@ -73,20 +202,64 @@ pub fn get_sets_json<'a>(from_local: bool) -> Result<String, &'a str> {
     // in case `from_local` is false, we perform a fresh data fetch from the API before we
     // return data.
     if !from_local {
-        update_sets().unwrap();
+        update_sets()?;
     }
 
     // in any case we will check if `set.json` already exisits. If it is not the case, 
     // we will perform an API fetch before (see `Err` branch).
-    match templates::set_service_templates::get_sets_json(game) {
-        Ok(sets) => Ok(sets),
+    match templates::set_service_templates::get_sets_json::<Set>(game) {
+        Ok(sets) => {
+            // outdated sets are returned anyway, but refreshed in the background for the next request
+            if from_local && templates::set_service_templates::sets_outdated(game) {
+                templates::set_service_templates::refresh_sets_in_background(&BACKGROUND_UPDATE, update_sets);
+            }
+            Ok(sets)
+        },
         Err(_) => {
-            update_sets().unwrap();
-            Ok(templates::set_service_templates::get_sets_json(game).unwrap())
+            // if the API is unreachable on first start, we fall back to the bundled set snapshot. Its fetch 
+            // metadata is reset, so that it is replaced by the next successful refresh. An empty snapshot
+            // is not stored, since the sets of all entries would be unknown until then.
+            if update_sets().is_err() {
+                let snapshot: Vec<Set> = load_set_snapshot();
+                if snapshot.is_empty() {
+                    return Err("Unable to fetch sets.");
+                }
+                store_sets(&snapshot).unwrap();
+                templates::set_service_templates::store_fetch_metadata(game, &SetFetchMetadata::default())?;
+            }
+            templates::set_service_templates::get_sets_json::<Set>(game)
         }
     }
 }
 
+/// Fetch all MTG sets from the API (see `update_sets`) and return a report of the
+/// differences to the previously stored sets as JSON string, including the collection entries
+/// that reference a renamed, changed or removed set.
+///
+/// # Argument
+/// `job`   - Job that runs the set update.
+///
+pub fn update_sets_with_report_json<'a>(job: &Job) -> Result<String, &'a str> {
+    let previous_sets: Vec<Set> = templates::set_service_templates::load_fetched_sets::<Set>("magic").unwrap_or_default();
+    let current_sets: Vec<Set> = update_sets_with_job(job)?;
+    templates::set_service_templates::get_set_update_report_json::<Set>("magic", &previous_sets, &current_sets)
+}
+
+/// Get all distinct set types of the fetched MTG sets, e.g. `core`, `expansion` or `token`,
+/// as a JSON list. These are the values that can be used to configure which sets are available
+/// for selection.
+///
+pub fn get_set_types_json<'a>() -> Result<String, &'a str> {
+    let sets: Vec<Set> = templates::set_service_templates::load_fetched_sets::<Set>("magic").unwrap_or_default();
+    let mut set_types: Vec<String> = sets.into_iter().filter_map(|set| set.set_type).collect();
+    set_types.sort();
+    set_types.dedup();
+    match serde_json::to_string(&set_types) {
+        Ok(json) => Ok(json),
+        Err(_) => Err("Unable to serialize set types to JSON.")
+    }
+}
+
 /// Store the provided set data in JSON format at `<storage_location>/magic/sets.json`.
 ///
 /// # Argument
@@ -95,3 +268,42 @@ pub fn get_sets_json<'a>(from_local: bool) -> Result<String, &'a str> {
 pub fn store_sets<'a>(sets: &Vec<Set>) -> Result<(), &'a str> {
     templates::set_service_templates::store_sets::<Set>("magic", sets)
 }
+
+/// Add the provided set as a new user-defined MTG set, stored at
+/// `<storage_location>/magic/custom_sets.json`.
+///
+/// # Argument
+/// `obj`   - MTG set instance as JSON object.
+///
+pub fn add_custom_set<'a>(obj: &str) -> Result<(), &'a str> {
+    templates::set_service_templates::add_custom_set::<Set>("magic", obj)
+}
+
+/// Update the user-defined MTG set with the same id as the provided set instance.
+///
+/// # Argument
+/// `obj`   - MTG set instance as JSON object.
+///
+pub fn update_custom_set<'a>(obj: &str) -> Result<(), &'a str> {
+    templates::set_service_templates::update_custom_set::<Set>("magic", obj)
+}
+
+/// Delete the user-defined MTG set with the provided id.
+///
+/// # Argument
+/// `id`    - Id of the custom set that should be deleted.
+///
+pub fn delete_custom_set<'a>(id: &str) -> Result<(), &'a str> {
+    templates::set_service_templates::delete_custom_set::<Set>("magic", id)
+}
+
+/// Get the asset of the specified kind of the MTG set with the provided id as base-64 encoded
+/// string. Assets are cached at `<storage_location>/magic/set_assets/` for offline use.
+///
+/// # Arguments
+/// `set_id`    - Id of the set whose asset should be returned.
+/// `asset`     - Kind of the asset, i.e. the set symbol or the set logo.
+///
+pub fn get_set_asset_b64<'a>(set_id: &str, asset: &SetAsset) -> Result<String, &'a str> {
+    templates::set_asset_templates::get_set_asset_b64::<Set>("magic", set_id, asset)
+}
@@ -8,25 +8,126 @@ mod magic;
 mod util;
 mod templates;
 
-use tauri::{CustomMenuItem, Menu, Submenu};
-use util::enums::Game;
+use tauri::{AppHandle, CustomMenuItem, Menu, Submenu};
+use util::enums::{Game, SetAsset, ImageRole};
+use util::jobs::JobRegistry;
+use templates::image_templates::ImageEdit;
 
-#[tauri::command]
-fn get_sets<'a>(game: Game) -> Result<String, &'a str> {
+#[tauri::command(async)]
+fn get_sets(game: Game) -> Result<String, String> {
     match game {
         Game::Magic => magic::set_services::get_sets_json(true),
         Game::Pokemon => pokemon::set_services::get_sets_json(true)
+    }.map_err(String::from)
+}
+
+#[tauri::command]
+fn update_sets(game: Game, app: AppHandle) -> Result<u32, String> {
+    Ok(util::jobs::start_job(&app, "update_sets", move |job| {
+        match game {
+            Game::Magic => magic::set_services::update_sets_with_report_json(job),
+            Game::Pokemon => pokemon::set_services::update_sets_with_report_json(job)
+        }.map_err(String::from)
+    }))
+}
+
+#[tauri::command]
+fn get_set_types<'a>(game: Game) -> Result<String, &'a str> {
+    match game {
+        Game::Magic => magic::set_services::get_set_types_json(),
+        Game::Pokemon => pokemon::set_services::get_set_types_json()
+    }
+}
+
+#[tauri::command]
+fn add_custom_set<'a>(obj: &str, game: Game) -> Result<(), &'a str> {
+    match game {
+        Game::Magic => magic::set_services::add_custom_set(obj),
+        Game::Pokemon => pokemon::set_services::add_custom_set(obj)
+    }
+}
+
+#[tauri::command]
+fn update_custom_set<'a>(obj: &str, game: Game) -> Result<(), &'a str> {
+    match game {
+        Game::Magic => magic::set_services::update_custom_set(obj),
+        Game::Pokemon => pokemon::set_services::update_custom_set(obj)
+    }
+}
+
+#[tauri::command]
+fn delete_custom_set<'a>(id: &str, game: Game) -> Result<(), &'a str> {
+    match game {
+        Game::Magic => magic::set_services::delete_custom_set(id),
+        Game::Pokemon => pokemon::set_services::delete_custom_set(id)
     }
 }
 
 #[tauri::command]
-fn update_sets<'a>(game: Game) -> Result<String, &'a str> {
+fn get_set_asset_b64<'a>(set_id: &str, asset: SetAsset, game: Game) -> Result<String, &'a str> {
     match game {
-        Game::Magic => magic::set_services::get_sets_json(false),
-        Game::Pokemon => pokemon::set_services::get_sets_json(false)
+        Game::Magic => magic::set_services::get_set_asset_b64(set_id, &asset),
+        Game::Pokemon => pokemon::set_services::get_set_asset_b64(set_id, &asset)
     }
 }
 
+#[tauri::command(async)]
+fn get_catalog(set_id: String, game: Game) -> Result<String, String> {
+    match game {
+        Game::Magic => magic::catalog_services::get_catalog_json(&set_id),
+        Game::Pokemon => pokemon::catalog_services::get_catalog_json(&set_id)
+    }.map_err(String::from)
+}
+
+#[tauri::command(async)]
+fn search_catalog(query: String, set_id: Option<String>, limit: Option<usize>, game: Game) -> Result<String, String> {
+    let limit = limit.unwrap_or(20);
+    match game {
+        Game::Magic => magic::catalog_services::search_catalog_json(&query, set_id.as_deref(), limit),
+        Game::Pokemon => pokemon::catalog_services::search_catalog_json(&query, set_id.as_deref(), limit)
+    }.map_err(String::from)
+}
+
+#[tauri::command(async)]
+fn lookup_card(set: String, number: String, game: Game) -> Result<String, String> {
+    match game {
+        Game::Magic => magic::catalog_services::lookup_card_json(&set, &number),
+        Game::Pokemon => pokemon::catalog_services::lookup_card_json(&set, &number)
+    }.map_err(String::from)
+}
+
+#[tauri::command]
+fn update_catalog(set_id: String, game: Game, app: AppHandle) -> Result<u32, String> {
+    Ok(util::jobs::start_job(&app, "update_catalog", move |job| {
+        match game {
+            Game::Magic => magic::catalog_services::update_catalog_with_job(&set_id, job),
+            Game::Pokemon => pokemon::catalog_services::update_catalog_with_job(&set_id, job)
+        }.map_err(String::from)
+    }))
+}
+
+#[tauri::command]
+fn import_magic_bulk_data(path: Option<String>, app: AppHandle) -> Result<u32, String> {
+    Ok(util::jobs::start_job(&app, "import_magic_bulk_data", move |job| {
+        magic::bulk_services::import_bulk_data_with_job(path.as_deref(), job).map_err(String::from)
+    }))
+}
+
+#[tauri::command]
+fn get_magic_bulk_metadata<'a>() -> Result<String, &'a str> {
+    magic::bulk_services::get_bulk_metadata_json()
+}
+
+#[tauri::command(async)]
+fn get_magic_printings(id: String) -> Result<String, String> {
+    magic::bulk_services::get_printings_json(&id).map_err(String::from)
+}
+
+#[tauri::command(async)]
+fn get_magic_prices(id: String) -> Result<String, String> {
+    magic::bulk_services::get_prices_json(&id).map_err(String::from)
+}
+
 #[tauri::command]
 fn get_collection<'a>(game: Game) -> Result<String, &'a str> {
     match game {
@@ -44,7 +145,7 @@ fn add_card<'a>(obj: &str, game: Game) -> Result<u32, &'a str> {
 }
 
 #[tauri::command]
-fn update_card<'a>(obj: &str, game: Game) -> Result<(), &'a str> {
+fn update_card<'a>(obj: &str, game: Game) -> Result<String, &'a str> {
     match game {
         Game::Magic => magic::card_services::update_card(obj),
         Game::Pokemon => pokemon::card_services::update_card(obj)
@@ -59,6 +160,68 @@ fn delete_card<'a>(id: u32, game: Game) -> Result<(), &'a str> {
     }
 }
 
+#[tauri::command]
+fn migrate_set_references<'a>(game: Game) -> Result<(), &'a str> {
+    match game {
+        Game::Magic => magic::card_services::migrate_set_references(),
+        Game::Pokemon => pokemon::card_services::migrate_set_references()
+    }
+}
+
+#[tauri::command]
+fn get_set_reference_report<'a>(game: Game) -> Result<String, &'a str> {
+    match game {
+        Game::Magic => magic::card_services::get_set_reference_report_json(),
+        Game::Pokemon => pokemon::card_services::get_set_reference_report_json()
+    }
+}
+
+#[tauri::command]
+fn resolve_identities(game: Game, app: AppHandle) -> Result<u32, String> {
+    Ok(util::jobs::start_job(&app, "resolve_identities", move |job| {
+        match game {
+            Game::Magic => magic::card_services::resolve_identities_with_job(job),
+            Game::Pokemon => pokemon::card_services::resolve_identities_with_job(job)
+        }.map_err(String::from)
+    }))
+}
+
+#[tauri::command]
+fn rename_images(game: Game, app: AppHandle) -> Result<u32, String> {
+    Ok(util::jobs::start_job(&app, "rename_images", move |job| {
+        match game {
+            Game::Magic => magic::card_services::rename_images_with_job(job),
+            Game::Pokemon => pokemon::card_services::rename_images_with_job(job)
+        }.map_err(String::from)
+    }))
+}
+
+#[tauri::command]
+fn deduplicate_images(game: Game, app: AppHandle) -> Result<u32, String> {
+    Ok(util::jobs::start_job(&app, "deduplicate_images", move |job| {
+        match game {
+            Game::Magic => magic::card_services::deduplicate_images_with_job(job),
+            Game::Pokemon => pokemon::card_services::deduplicate_images_with_job(job)
+        }.map_err(String::from)
+    }))
+}
+
+#[tauri::command]
+fn check_images<'a>(game: Game) -> Result<String, &'a str> {
+    match game {
+        Game::Magic => magic::card_services::check_images_json(),
+        Game::Pokemon => pokemon::card_services::check_images_json()
+    }
+}
+
+#[tauri::command]
+fn repair_images<'a>(attach: Vec<String>, delete: Vec<String>, remove_dangling: bool, game: Game) -> Result<String, &'a str> {
+    match game {
+        Game::Magic => magic::card_services::repair_images_json(&attach, &delete, remove_dangling),
+        Game::Pokemon => pokemon::card_services::repair_images_json(&attach, &delete, remove_dangling)
+    }
+}
+
 #[tauri::command]
 fn copy_image<'a>(obj: &str, img_location: &str, new_entry: bool, game: Game) -> Result<String, &'a str> {
     match game {
@@ -68,21 +231,53 @@ fn copy_image<'a>(obj: &str, img_location: &str, new_entry: bool, game: Game) ->
 }
 
 #[tauri::command]
-fn get_image_b64<'a>(image: &str, game: Game) -> Result<String, &'a str> {
+fn discard_image<'a>(image: &str, game: Game) -> Result<(), &'a str> {
+    match game {
+        Game::Magic => magic::card_services::discard_image(image),
+        Game::Pokemon => pokemon::card_services::discard_image(image)
+    }
+}
+
+#[tauri::command]
+fn reorder_images<'a>(id: u32, images: Vec<String>, game: Game) -> Result<String, &'a str> {
+    match game {
+        Game::Magic => magic::card_services::reorder_images(&id, images),
+        Game::Pokemon => pokemon::card_services::reorder_images(&id, images)
+    }
+}
+
+#[tauri::command]
+fn set_image_role<'a>(id: u32, image: &str, role: Option<ImageRole>, game: Game) -> Result<String, &'a str> {
     match game {
-        Game::Magic => magic::card_services::get_image_b64(image),
-        Game::Pokemon => pokemon::card_services::get_image_b64(image)
+        Game::Magic => magic::card_services::set_image_role(&id, image, role),
+        Game::Pokemon => pokemon::card_services::set_image_role(&id, image, role)
     }
 }
 
 #[tauri::command]
-fn delete_image<'a>(image: &str, game: Game) -> Result<(), &'a str> {
+fn set_primary_image<'a>(id: u32, image: Option<&str>, game: Game) -> Result<String, &'a str> {
     match game {
-        Game::Magic => magic::card_services::delete_image(image),
-        Game::Pokemon => pokemon::card_services::delete_image(image)
+        Game::Magic => magic::card_services::set_primary_image(&id, image),
+        Game::Pokemon => pokemon::card_services::set_primary_image(&id, image)
     }
 }
 
+#[tauri::command(async)]
+fn edit_image(id: u32, image: String, edit: ImageEdit, game: Game) -> Result<String, String> {
+    match game {
+        Game::Magic => magic::card_services::edit_image(&id, &image, &edit),
+        Game::Pokemon => pokemon::card_services::edit_image(&id, &image, &edit)
+    }.map_err(String::from)
+}
+
+#[tauri::command(async)]
+fn undo_image_edit(id: u32, image: String, game: Game) -> Result<String, String> {
+    match game {
+        Game::Magic => magic::card_services::undo_image_edit(&id, &image),
+        Game::Pokemon => pokemon::card_services::undo_image_edit(&id, &image)
+    }.map_err(String::from)
+}
+
 
 fn main() {
     // configure menu
@@ -100,11 +295,38 @@ fn main() {
     // update menu
     let update_sets_pokemon = CustomMenuItem::new("update/sets/pokemon".to_string(), "Update Pokemon");
     let update_sets_magic = CustomMenuItem::new("update/sets/magic".to_string(), "Update Magic");
-    let update_menu = Submenu::new("Sets", Menu::new().add_item(update_sets_pokemon).add_item(update_sets_magic));
+    let download_bulk_magic = CustomMenuItem::new("update/bulk/magic".to_string(), "Download Magic Card Data");
+    let import_bulk_magic = CustomMenuItem::new("update/bulk/magic/file".to_string(), "Import Magic Card Data from File");
+    let update_menu = Submenu::new("Sets", Menu::new()
+        .add_item(update_sets_pokemon)
+        .add_item(update_sets_magic)
+        .add_item(download_bulk_magic)
+        .add_item(import_bulk_magic));
+
+    // collection menu
+    let resolve_identities_pokemon = CustomMenuItem::new("resolve_identities/pokemon".to_string(), "Link Pokemon Cards");
+    let resolve_identities_magic = CustomMenuItem::new("resolve_identities/magic".to_string(), "Link Magic Cards");
+    let rename_images_pokemon = CustomMenuItem::new("rename_images/pokemon".to_string(), "Rename Pokemon Images");
+    let rename_images_magic = CustomMenuItem::new("rename_images/magic".to_string(), "Rename Magic Images");
+    let check_images_pokemon = CustomMenuItem::new("check_images/pokemon".to_string(), "Check Pokemon Images");
+    let check_images_magic = CustomMenuItem::new("check_images/magic".to_string(), "Check Magic Images");
+    let deduplicate_images_pokemon = CustomMenuItem::new("deduplicate_images/pokemon".to_string(), "Deduplicate Pokemon Images");
+    let deduplicate_images_magic = CustomMenuItem::new("deduplicate_images/magic".to_string(), "Deduplicate Magic Images");
+    let collection_menu = Submenu::new("Collection", Menu::new()
+        .add_item(resolve_identities_pokemon)
+        .add_item(resolve_identities_magic)
+        .add_item(rename_images_pokemon)
+        .add_item(rename_images_magic)
+        .add_item(check_images_pokemon)
+        .add_item(check_images_magic)
+        .add_item(deduplicate_images_pokemon)
+        .add_item(deduplicate_images_magic));
 
-    let menu = Menu::new().add_submenu(file_menu).add_submenu(game_menu).add_submenu(update_menu);
+    let menu = Menu::new().add_submenu(file_menu).add_submenu(game_menu).add_submenu(update_menu).add_submenu(collection_menu);
 
     tauri::Builder::default()
+        .manage(JobRegistry::default())
+        .register_uri_scheme_protocol(util::protocol::URI_SCHEME, util::protocol::handle_request)
         .menu(menu)
         .on_menu_event(|event| match event.menu_item_id() {
             "quit" => {
@@ -118,15 +340,42 @@ fn main() {
             util::enums::get_condition_variants_json,
             util::enums::get_language_variants_json,
             util::enums::get_game_variants_json,
+            util::enums::get_image_role_variants_json,
+            util::jobs::get_running_jobs_json,
+            util::jobs::cancel_job,
             add_card,
             get_sets,
             update_sets,
+            get_set_types,
+            add_custom_set,
+            update_custom_set,
+            delete_custom_set,
+            get_set_asset_b64,
+            get_catalog,
+            update_catalog,
+            search_catalog,
+            lookup_card,
+            import_magic_bulk_data,
+            get_magic_bulk_metadata,
+            get_magic_printings,
+            get_magic_prices,
             get_collection,
             copy_image,
-            get_image_b64,
-            delete_image,
+            discard_image,
+            edit_image,
+            undo_image_edit,
+            reorder_images,
+            set_image_role,
+            set_primary_image,
             delete_card,
-            update_card
+            update_card,
+            migrate_set_references,
+            get_set_reference_report,
+            resolve_identities,
+            rename_images,
+            check_images,
+            repair_images,
+            deduplicate_images
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
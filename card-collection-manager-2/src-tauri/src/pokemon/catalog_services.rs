@@ -0,0 +1,127 @@
+use serde::{Serialize, Deserialize};
+
+use crate::templates;
+use crate::templates::catalog_service_templates::CatalogCard;
+use crate::util::config::{Configuration, load_configuration};
+use crate::util::http;
+use crate::util::jobs::Job;
+
+use super::set_services::{Set, get_api_headers};
+
+/// number of cards that are requested per page from `https://api.pokemontcg.io`
+const CARD_PAGE_SIZE: usize = 250;
+
+/// Get the card catalog of the Pokemon TCG set with the provided id as JSON list. The catalog is cached at
+/// `<storage_location>/pokemon/catalog/<set>.json` and only fetched, if it is not cached yet.
+/// 
+/// # Argument
+/// `set_id`    - Id of the set whose catalog should be returned.
+/// 
+pub fn get_catalog_json<'a>(set_id: &str) -> Result<String, &'a str> {
+    let catalog = templates::catalog_service_templates::get_catalog("pokemon", set_id, fetch_catalog, &Job::detached())?;
+    match serde_json::to_string(&catalog) {
+        Ok(json) => Ok(json),
+        Err(_) => Err("Unable to serialize catalog to JSON.")
+    }
+}
+
+/// Fetch the card catalog of the Pokemon TCG set with the provided id, replace the cached catalog of the
+/// set and return it as JSON list.
+/// 
+/// # Arguments
+/// `set_id`    - Id of the set whose catalog should be updated.
+/// `job`       - Job that runs the catalog update.
+/// 
+pub fn update_catalog_with_job<'a>(set_id: &str, job: &Job) -> Result<String, &'a str> {
+    let catalog = templates::catalog_service_templates::update_catalog("pokemon", set_id, fetch_catalog, job)?;
+    match serde_json::to_string(&catalog) {
+        Ok(json) => Ok(json),
+        Err(_) => Err("Unable to serialize catalog to JSON.")
+    }
+}
+
+/// Search the cached Pokemon TCG card catalogs for cards whose name matches the provided query and return
+/// the matches as JSON list, best matches first. The search ignores case and accents and tolerates typos.
+/// 
+/// # Arguments
+/// `query`     - (Partial) card name to search for.
+/// `set_id`    - Id of the set whose catalog should be searched. If not provided, all cached catalogs are searched.
+/// `limit`     - Maximum number of returned matches.
+/// 
+pub fn search_catalog_json<'a>(query: &str, set_id: Option<&str>, limit: usize) -> Result<String, &'a str> {
+    let matches = templates::catalog_service_templates::search_catalog::<Set>("pokemon", query, set_id, limit, fetch_catalog, &Job::detached())?;
+    match serde_json::to_string(&matches) {
+        Ok(json) => Ok(json),
+        Err(_) => Err("Unable to serialize catalog matches to JSON.")
+    }
+}
+
+/// Look up the Pokemon TCG card with the provided collector number within the set with the provided id
+/// or code, e.g. `base1` and `4`, and return it as JSON object. If there is no such card, `null` is returned.
+/// 
+/// # Arguments
+/// `set`       - Id or code of the set.
+/// `number`    - Collector number of the card within the set.
+/// 
+pub fn lookup_card_json<'a>(set: &str, number: &str) -> Result<String, &'a str> {
+    let card = templates::catalog_service_templates::lookup_card::<Set>("pokemon", set, number, fetch_catalog, &Job::detached())?;
+    match serde_json::to_string(&card) {
+        Ok(json) => Ok(json),
+        Err(_) => Err("Unable to serialize card to JSON.")
+    }
+}
+
+/// Fetch all cards of the Pokemon TCG set with the provided id.
+/// 
+/// This function call the REST-API at `https://api.pokemontcg.io` (or the API URL from the
+/// configuration) to retrieve the data. The cards are fetched page by page until all cards
+/// are retrieved.
+/// 
+pub fn fetch_catalog<'a>(set_id: &str, job: &Job) -> Result<Vec<CatalogCard>, &'a str> {
+
+    #[derive(Serialize, Deserialize, Debug)]
+    struct TmpCard {
+        pub id: String,
+        pub name: String,
+        pub number: String,
+        pub rarity: Option<String>
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    struct Response {
+        data: Vec<TmpCard>,
+        #[serde(rename = "totalCount")]
+        total_count: usize
+    }
+
+    let config: Configuration = load_configuration().unwrap();
+    let url = format!("{}/cards", &config.pokemon.api_url);
+    let headers = get_api_headers(&config);
+
+    let mut catalog: Vec<CatalogCard> = Vec::new();
+    let mut page: usize = 0;
+    loop {
+        job.check_cancelled()?;
+        page += 1;
+        let query = [
+            ("q", format!("set.id:{}", set_id)),
+            ("page", page.to_string()),
+            ("pageSize", CARD_PAGE_SIZE.to_string()),
+            ("select", String::from("id,name,number,rarity"))
+        ];
+        let resp: Response = http::get_json::<Response>(&url, &query, &headers)?;
+        let page_len = resp.data.len();
+        catalog.extend(resp.data.into_iter().map(|card| CatalogCard {
+            id: card.id,
+            name: card.name,
+            number: card.number,
+            rarity: card.rarity
+        }));
+        job.report_progress(catalog.len() as f32 / resp.total_count.max(1) as f32, "Fetching Pokemon cards");
+
+        if page_len == 0 || catalog.len() >= resp.total_count {
+            break;
+        }
+    }
+    Ok(catalog)
+}
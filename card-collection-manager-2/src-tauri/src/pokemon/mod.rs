@@ -1,2 +1,3 @@
 pub mod card_services;
-pub mod set_services;
\ No newline at end of file
+pub mod set_services;
+pub mod catalog_services;
\ No newline at end of file
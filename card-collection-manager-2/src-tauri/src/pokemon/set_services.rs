@@ -1,5 +1,47 @@
 use serde::{Serialize, Deserialize};
+use std::sync::atomic::AtomicBool;
 use crate::templates;
+use crate::templates::set_service_templates::{SetEntry, SetFetchMetadata};
+use crate::util::enums::SetAsset;
+use crate::util::config::{Configuration, load_configuration};
+use crate::util::http;
+use crate::util::jobs::Job;
+
+/// number of sets that are requested per page from `https://api.pokemontcg.io`
+const SET_PAGE_SIZE: usize = 250;
+
+/// flag that indicates whether a background update of the sets is running
+static BACKGROUND_UPDATE: AtomicBool = AtomicBool::new(false);
+
+/// Pokemon TCG set information as provided by the API
+#[derive(Serialize, Deserialize, Debug)]
+struct TmpSet {
+    pub id: String,
+    pub name: String,
+    pub series: Option<String>,
+    #[serde(rename = "printedTotal")]
+    pub printed_total: Option<u32>,
+    pub total: Option<u32>,
+    #[serde(rename = "releaseDate")]
+    pub release_date: String,
+    #[serde(rename = "ptcgoCode")]
+    pub ptcgo_code: Option<String>,
+    pub images: Option<TmpSetImages>
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct TmpSetImages {
+    pub symbol: Option<String>,
+    pub logo: Option<String>
+}
+
+/// Response of the API for a page of sets
+#[derive(Serialize, Deserialize, Debug)]
+struct Response {
+    data: Vec<TmpSet>,
+    #[serde(rename = "totalCount")]
+    total_count: usize
+}
 
 // Pokemon TCG set information
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -10,37 +52,182 @@ pub struct Set {
     pub name: String,
     /// release date in format YYYY/MM/DD
     #[serde(rename = "releaseDate")]
-    pub release_date: String
+    pub release_date: String,
+    /// set code as printed on the cards, e.g. `DRI`
+    #[serde(rename = "ptcgoCode", default)]
+    pub ptcgo_code: Option<String>,
+    /// series the set belongs to, e.g. `Sword & Shield`
+    #[serde(default)]
+    pub series: Option<String>,
+    /// number of cards as printed on the cards, excluding secret rares
+    #[serde(rename = "printedTotal", default)]
+    pub printed_total: Option<u32>,
+    /// total number of cards within the set, including secret rares
+    #[serde(default)]
+    pub total: Option<u32>,
+    /// URL of the set symbol image
+    #[serde(rename = "symbolUrl", default)]
+    pub symbol_url: Option<String>,
+    /// URL of the set logo image
+    #[serde(rename = "logoUrl", default)]
+    pub logo_url: Option<String>,
+    /// flag to indicate a user-defined set that is not provided by the API
+    #[serde(default)]
+    pub custom: bool
+}
+
+/// implementation of the Set struct that is used by templates
+/// to enable generic handling.
+impl SetEntry for Set {
+    fn get_id(&self) -> &str {
+        &self.id
+    }
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+    fn get_release_date(&self) -> &str {
+        &self.release_date
+    }
+    fn get_code(&self) -> Option<&str> {
+        self.ptcgo_code.as_deref()
+    }
+    fn set_custom(&mut self, custom: bool) {
+        self.custom = custom;
+    }
+    fn get_symbol_url(&self) -> Option<&str> {
+        self.symbol_url.as_deref()
+    }
+    fn get_logo_url(&self) -> Option<&str> {
+        self.logo_url.as_deref()
+    }
+    fn is_selectable(&self, _config: &Configuration) -> bool {
+        true
+    }
 }
 
 /// Get all Pokemon TCG sets as vector of `Set` instances.
 /// Implicitly, this function also stores the resulting data in JSON format 
 /// at `<storage_location>/pokemon/sets.json`.
 /// 
-/// This function call the REST-API at `https://api.pokemontcg.io` to retrieve the data.
+/// This function call the REST-API at `https://api.pokemontcg.io` (or the API URL from the
+/// configuration) to retrieve the data.
+/// The sets are fetched page by page until all sets are retrieved. If an API key is configured,
+/// it is sent along with each request.
 /// 
 pub fn update_sets<'a>() -> Result<Vec<Set>, &'a str> {
+    update_sets_with_job(&Job::detached())
+}
+
+/// Same as `update_sets`, but reports its progress to the provided job and stops without
+/// storing any data, if the job gets cancelled.
+/// 
+/// # Argument
+/// `job`   - Job that runs the set update.
+/// 
+pub fn update_sets_with_job<'a>(job: &Job) -> Result<Vec<Set>, &'a str> {
+
+    let game = "pokemon";
+    let config: Configuration = load_configuration().unwrap();
+    let url = format!("{}/sets", &config.pokemon.api_url);
+    let headers = get_api_headers(&config);
+
+    // the API returns the sets in pages. The first page is requested conditionally, if it has not been 
+    // modified since the last fetch, we keep the local sets. Otherwise, we keep fetching until all sets
+    // are retrieved.
+    let page_query = |page: usize| [("page", page.to_string()), ("pageSize", SET_PAGE_SIZE.to_string())];
+    job.report_progress(0.0, "Fetching Pokemon sets");
+    let (first_page, metadata) = match templates::set_service_templates::fetch_sets::<Response>(game, &url, &page_query(1), &headers) {
+        Ok(Some(fetched)) => fetched,
+        Ok(None) => {
+            job.report_progress(1.0, "Pokemon sets are up to date");
+            return templates::set_service_templates::load_fetched_sets::<Set>(game);
+        },
+        Err(_) => return Err("Unable to fetch Pokemon sets.")
+    };
+
+    let total_count = first_page.total_count;
+    let mut fetched_sets: Vec<TmpSet> = first_page.data;
+    let mut page: usize = 1;
+    while fetched_sets.len() < total_count {
+        job.check_cancelled()?;
+        job.report_progress(fetched_sets.len() as f32 / total_count as f32, "Fetching Pokemon sets");
+        page += 1;
+        let resp: Response = match http::get_json::<Response>(&url, &page_query(page), &headers) {
+            Ok(resp) => resp,
+            Err(_) => return Err("Unable to fetch Pokemon sets.")
+        };
+        if resp.data.is_empty() {
+            break;
+        }
+        fetched_sets.extend(resp.data);
+    }
+
+    job.check_cancelled()?;
+
+    let prepared_sets = prepare_sets(fetched_sets);
+    store_sets(&prepared_sets).unwrap();
+    templates::set_service_templates::store_fetch_metadata(game, &metadata)?;
+    job.report_progress(1.0, "Pokemon sets updated");
+    Ok(prepared_sets)
+}
 
-    #[derive(Serialize, Deserialize, Debug)]
-    struct Response {
-        data: Vec<Set>
+/// Get the request headers for the API at `https://api.pokemontcg.io`, i.e. the API key
+/// from the configuration, if there is one.
+/// 
+/// # Argument
+/// `config`    - App configuration
+/// 
+pub fn get_api_headers(config: &Configuration) -> Vec<(&str, &str)> {
+    let mut headers: Vec<(&str, &str)> = Vec::new();
+    if let Some(api_key) = config.pokemon.api_key.as_ref().filter(|api_key| !api_key.is_empty()) {
+        headers.push(("X-Api-Key", api_key));
     }
+    headers
+}
+
+/// Map the provided sets as provided by the API to instances of `Set`.
+fn prepare_sets(sets: Vec<TmpSet>) -> Vec<Set> {
+    sets
+        .into_iter()
+        .map(|set| Set {
+            id: set.id,
+            name: set.name,
+            release_date: set.release_date,
+            ptcgo_code: set.ptcgo_code,
+            series: set.series,
+            printed_total: set.printed_total,
+            total: set.total,
+            symbol_url: set.images.as_ref().and_then(|images| images.symbol.clone()),
+            logo_url: set.images.as_ref().and_then(|images| images.logo.clone()),
+            custom: false
+        })
+        .collect::<Vec<_>>()
+}
 
-    let resp = reqwest::blocking::get("https://api.pokemontcg.io/v2/sets").unwrap().json::<Response>().unwrap();
-    store_sets(&resp.data).unwrap();
-    Ok(resp.data)
+/// Get the Pokemon TCG sets from the snapshot at `src-tauri/snapshots`, that is embedded into the app.
+/// The snapshot is used on first start, if the API is unreachable.
+fn load_set_snapshot() -> Vec<Set> {
+    let snapshot: Response = serde_json::from_str(include_str!("../../snapshots/pokemon_sets.json"))
+        .expect("Unable to deserialize set snapshot.");
+    prepare_sets(snapshot.data)
 }
 
 /// Get all Pokemon TCG from either the locally stored file at `<storage_location>/pokemon/sets.json`
 /// or a fresh fetch from the corresponding API, in JSON format as a string.
 /// If the file does not exist, it will automatically fetch the data from the
 /// REST-API, store the result in the `sets.json` file and return the data as JSON.
+/// If the REST-API is unreachable in this case, the set snapshot bundled with the app is used.
+/// If the local file is older than the maximum age from the configuration, its data is returned
+/// and a refresh is started in the background.
+/// User-defined sets from `<storage_location>/pokemon/custom_sets.json` are always merged into
+/// the result, with their field `custom` set to `true`.
 /// 
 /// # Arguments
 /// `from_local`    - If `true`, the function will try to access the local `set.json` file and only
 ///                   fetch the API, if it cannot find this file. If `false`, it will fetch the API
 ///                   for set data, store it in the local `set.json` file and then provide its content
-///                   as a JSON string.
+///                   as a JSON string. The API is requested conditionally, so unchanged set data is
+///                   not downloaded again.
 /// 
 pub fn get_sets_json<'a>(from_local: bool) -> Result<String, &'a str> {
 
@@ -49,20 +236,56 @@ pub fn get_sets_json<'a>(from_local: bool) -> Result<String, &'a str> {
     // in case `from_local` is false, we perform a fresh data fetch from the API before we
     // return data.
     if !from_local {
-        update_sets().unwrap();
+        update_sets()?;
     }
 
     // in any case we will check if `set.json` already exisits. If it is not the case, 
     // we will perform an API fetch before (see `Err` branch).
-    match templates::set_service_templates::get_sets_json(game) {
-        Ok(sets) => Ok(sets),
-        Err(_)  => {
-            update_sets().unwrap();
-            Ok(templates::set_service_templates::get_sets_json(game).unwrap())
+    match templates::set_service_templates::get_sets_json::<Set>(game) {
+        Ok(sets) => {
+            // outdated sets are returned anyway, but refreshed in the background for the next request
+            if from_local && templates::set_service_templates::sets_outdated(game) {
+                templates::set_service_templates::refresh_sets_in_background(&BACKGROUND_UPDATE, update_sets);
+            }
+            Ok(sets)
+        },
+        Err(_) => {
+            // if the API is unreachable on first start, we fall back to the bundled set snapshot. Its fetch 
+            // metadata is reset, so that it is replaced by the next successful refresh. An empty snapshot
+            // is not stored, since the sets of all entries would be unknown until then.
+            if update_sets().is_err() {
+                let snapshot: Vec<Set> = load_set_snapshot();
+                if snapshot.is_empty() {
+                    return Err("Unable to fetch sets.");
+                }
+                store_sets(&snapshot).unwrap();
+                templates::set_service_templates::store_fetch_metadata(game, &SetFetchMetadata::default())?;
+            }
+            templates::set_service_templates::get_sets_json::<Set>(game)
         }
     }
 }
 
+/// Fetch all Pokemon TCG sets from the API (see `update_sets`) and return a report of the
+/// differences to the previously stored sets as JSON string, including the collection entries
+/// that reference a renamed, changed or removed set.
+///
+/// # Argument
+/// `job`   - Job that runs the set update.
+///
+pub fn update_sets_with_report_json<'a>(job: &Job) -> Result<String, &'a str> {
+    let previous_sets: Vec<Set> = templates::set_service_templates::load_fetched_sets::<Set>("pokemon").unwrap_or_default();
+    let current_sets: Vec<Set> = update_sets_with_job(job)?;
+    templates::set_service_templates::get_set_update_report_json::<Set>("pokemon", &previous_sets, &current_sets)
+}
+
+/// Get all distinct set types of the Pokemon TCG sets as a JSON list. The API at 
+/// `https://api.pokemontcg.io` does not provide set types, so this list is always empty.
+/// 
+pub fn get_set_types_json<'a>() -> Result<String, &'a str> {
+    Ok(String::from("[]"))
+}
+
 /// Store the provided set data in JSON format at `<storage_location>/pokemon/sets.json`.
 /// 
 /// # Argument
@@ -70,4 +293,43 @@ pub fn get_sets_json<'a>(from_local: bool) -> Result<String, &'a str> {
 /// 
 pub fn store_sets<'a>(sets: &Vec<Set>) -> Result<(), &'a str> {
     templates::set_service_templates::store_sets::<Set>("pokemon", sets)
-}
\ No newline at end of file
+}
+
+/// Add the provided set as a new user-defined Pokemon TCG set, stored at
+/// `<storage_location>/pokemon/custom_sets.json`.
+///
+/// # Argument
+/// `obj`   - Pokemon TCG set instance as JSON object.
+///
+pub fn add_custom_set<'a>(obj: &str) -> Result<(), &'a str> {
+    templates::set_service_templates::add_custom_set::<Set>("pokemon", obj)
+}
+
+/// Update the user-defined Pokemon TCG set with the same id as the provided set instance.
+///
+/// # Argument
+/// `obj`   - Pokemon TCG set instance as JSON object.
+///
+pub fn update_custom_set<'a>(obj: &str) -> Result<(), &'a str> {
+    templates::set_service_templates::update_custom_set::<Set>("pokemon", obj)
+}
+
+/// Delete the user-defined Pokemon TCG set with the provided id.
+///
+/// # Argument
+/// `id`    - Id of the custom set that should be deleted.
+///
+pub fn delete_custom_set<'a>(id: &str) -> Result<(), &'a str> {
+    templates::set_service_templates::delete_custom_set::<Set>("pokemon", id)
+}
+
+/// Get the asset of the specified kind of the Pokemon TCG set with the provided id as base-64 encoded
+/// string. Assets are cached at `<storage_location>/pokemon/set_assets/` for offline use.
+///
+/// # Arguments
+/// `set_id`    - Id of the set whose asset should be returned.
+/// `asset`     - Kind of the asset, i.e. the set symbol or the set logo.
+///
+pub fn get_set_asset_b64<'a>(set_id: &str, asset: &SetAsset) -> Result<String, &'a str> {
+    templates::set_asset_templates::get_set_asset_b64::<Set>("pokemon", set_id, asset)
+}
@@ -1,19 +1,149 @@
 use std::io::{Read, Write};
 use std::collections::HashMap;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::ffi::OsStr;
-use std::fs::{File, create_dir, copy, remove_file};
-use serde::{Serialize};
+use std::fs::{File, create_dir, copy, read_dir, remove_file, rename};
+use serde::{Serialize, Deserialize};
 use serde::de::DeserializeOwned;
-use image::{DynamicImage, ImageOutputFormat};
-use std::io::Cursor;
+use serde_json::Value;
 
 use crate::util::config::{Configuration, load_configuration};
+use crate::util::enums::{Language, ImageRole};
 use crate::util::collection::{MapEntryWithId, add_map_entry, get_next_id};
-use crate::util::fs::format_text_for_fs;
+use crate::util::fs::{format_text_for_fs, get_unique_filename, parse_index_from_filename};
+use crate::util::jobs::Job;
+use crate::templates::set_service_templates::{SetEntry, load_sets};
+use crate::templates::thumbnail_templates::{generate_thumbnails_in_background, get_content_hash, remove_thumbnails, rename_thumbnails};
+use crate::templates::image_index_templates::{DeduplicationReport, StoredImage, find_image_by_hash, add_image_reference, release_image_reference, get_image_references, rename_indexed_image, rebuild_image_index, get_deduplication_report, get_data_hash};
+use crate::templates::image_templates::{ImageEdit, ImageEditResult, normalize_image, store_original, remove_original, remove_image_history, rename_image_files, copy_image_history, edit_image, undo_image_edit};
+use crate::templates::catalog_service_templates::{CatalogCard, get_catalog, normalize_name, normalize_number};
+
+/// File extensions of temporary files within the image directory, e.g. of an image that is edited
+/// (`.tmp`, see `edit_image`) or renamed (`.rename`, see `rename_entry_images`)
+const TEMPORARY_IMAGE_EXTENSIONS: [&str; 2] = ["tmp", "rename"];
+
+/// Trait that an entry type needs to implement in order to be stored with a reference to
+/// its set instead of an embedded copy of the set. Within the collection file, the field `set`
+/// of an entry only holds the set id, which is resolved against the game's sets at read time.
+/// 
+pub trait EntryWithSet {
+    type Set: Serialize + DeserializeOwned + SetEntry;
+}
+
+/// Trait that an entry type needs to implement in order to be linked to its card identity
+/// within the game's API, e.g. the Scryfall card id of a Magic card.
+/// 
+pub trait EntryWithIdentity {
+    fn get_name(&self) -> &str;
+    fn get_set_id(&self) -> &str;
+    fn get_set_no(&self) -> &str;
+    fn get_upstream_id(&self) -> Option<&str>;
+    fn set_upstream_id(&mut self, upstream_id: Option<String>);
+}
+
+/// Trait that an entry type needs to implement in order to name its images according to the
+/// configured filename template and to rename them, when the entry changes.
+/// 
+pub trait EntryWithImages: EntryWithSet + EntryWithIdentity {
+    fn get_set(&self) -> &Self::Set;
+    fn get_language(&self) -> &Language;
+    fn get_images(&self) -> &Vec<String>;
+    fn set_images(&mut self, images: Vec<String>);
+    fn get_image_roles(&self) -> &HashMap<String, ImageRole>;
+    fn set_image_roles(&mut self, image_roles: HashMap<String, ImageRole>);
+    fn get_primary_image(&self) -> Option<&str>;
+    fn set_primary_image(&mut self, primary_image: Option<String>);
+}
+
+/// Result of applying the filename template to the images of a collection.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ImageRenameReport {
+    /// number of images that were renamed
+    pub renamed: u32,
+    /// number of images that already had the name of the template
+    pub unchanged: u32,
+    /// images that could not be renamed, since they do not exist or their new name is used by another image of the entry
+    pub failed: Vec<String>
+}
+
+/// Image within the image directory of a game that no entry of the collection references.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct OrphanedImage {
+    /// name of the image
+    pub image: String,
+    /// id of the entry that the name of the image suggests, e.g. `12` for `12+Alpha+Lightning Bolt+0.jpg`
+    #[serde(rename = "suggestedEntryId")]
+    pub suggested_entry_id: Option<u32>
+}
+
+/// Image reference of an entry, whose image does not exist within the image directory of the game.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DanglingImage {
+    /// id of the entry that references the image
+    #[serde(rename = "entryId")]
+    pub entry_id: u32,
+    /// name of the missing image
+    pub image: String
+}
+
+/// Result of checking the images of a collection against the image directory of the game.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ImageCheckReport {
+    /// images that no entry references
+    pub orphans: Vec<OrphanedImage>,
+    /// image references of entries, whose images do not exist
+    pub dangling: Vec<DanglingImage>
+}
+
+/// Collection entry that could not be linked to its card identity, as it is listed within
+/// the identity report.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UnresolvedEntry {
+    /// id of the entry within the collection
+    pub id: u32,
+    /// name of the entry
+    pub name: String,
+    /// id of the set the entry refers to
+    #[serde(rename = "setId")]
+    pub set_id: String,
+    /// collector number of the entry within its set
+    #[serde(rename = "setNo")]
+    pub set_no: String,
+    /// reason why the entry could not be linked
+    pub reason: String,
+    /// catalog cards that might match the entry
+    pub candidates: Vec<CatalogCard>
+}
+
+/// Result of linking the entries of a collection to their card identities.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct IdentityReport {
+    /// number of entries that were linked by this run
+    pub resolved: usize,
+    /// number of entries that were already linked before
+    #[serde(rename = "alreadyLinked")]
+    pub already_linked: usize,
+    /// entries that match several catalog cards or whose name and number match different cards
+    pub ambiguous: Vec<UnresolvedEntry>,
+    /// entries that do not match any catalog card
+    pub unmatched: Vec<UnresolvedEntry>
+}
+
+/// Reference of a collection entry to its set, as it is listed within set related reports.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EntrySetReference {
+    /// id of the entry within the collection
+    pub id: u32,
+    /// name of the entry
+    pub name: String,
+    /// id of the set the entry refers to
+    #[serde(rename = "setId")]
+    pub set_id: String
+}
 
 /// Add the povided object as a new entry to the collection of the provided game, store the new update
-/// to the collection file of the game and return the new id of the entry in the collection.
+/// to the collection file of the game and return the new id of the entry in the collection. The entry
+/// adds a reference to each of its images, see `copy_image`.
 /// 
 /// # Arguments
 /// `game`  - Game name to specifiy to which collection the entry should be added
@@ -22,8 +152,12 @@ use crate::util::fs::format_text_for_fs;
 /// # Returns
 /// New id of the entry withing the collection
 /// 
-pub fn add_entry_to_collection<'a, T: Serialize + DeserializeOwned + MapEntryWithId>(game: &str, obj: &str) -> Result<u32, &'a str> {
-    let entry: T = serde_json::from_str(obj).expect("Unable to deserialize entry object.");
+pub fn add_entry_to_collection<'a, T: Serialize + DeserializeOwned + MapEntryWithId + EntryWithImages>(game: &str, obj: &str) -> Result<u32, &'a str> {
+    let mut entry: T = serde_json::from_str(obj).expect("Unable to deserialize entry object.");
+    retain_image_metadata(&mut entry);
+    for image in entry.get_images().iter() {
+        add_image_reference(game, image)?;
+    }
     let mut collection: HashMap<u32, T> = load_collection::<T>(game).unwrap();
     let id: u32 = add_map_entry::<T>(&mut collection, entry);
     store_collection::<T>(game, &collection).expect("Unable to store updated collection.");
@@ -31,20 +165,54 @@ pub fn add_entry_to_collection<'a, T: Serialize + DeserializeOwned + MapEntryWit
 }
 
 /// Update an existing entry in the collection of the provided game, with the provided object.
-/// The id within the provided object specficies, which existing entry should be updated.
+/// The id within the provided object specficies, which existing entry should be updated. Images that
+/// were added to the entry add a reference, images that were removed from the entry are deleted, unless
+/// other entries share them. The images of the entry
+/// are renamed according to the filename template, e.g. if the name of the entry changed.
 /// 
 /// # Arguments
 /// `game`  - Game name to specifiy in which collection the entry should be updated
 /// `obj`   - entry as JSON string
 /// 
-pub fn update_entry_in_collection<'a, T: Serialize + DeserializeOwned + MapEntryWithId>(game: &str, obj: &str) -> Result<(), &'a str> {
-    let entry: T = serde_json::from_str(obj).expect("Unable to deserialize entry object.");
+/// # Returns
+/// The updated entry with the new names of its images
+/// 
+pub fn update_entry_in_collection<'a, T: Serialize + DeserializeOwned + Clone + MapEntryWithId + EntryWithImages>(game: &str, obj: &str) -> Result<T, &'a str> {
+    let mut entry: T = serde_json::from_str(obj).expect("Unable to deserialize entry object.");
     let mut collection: HashMap<u32, T> = load_collection::<T>(game).unwrap();
     let id: u32 = entry.get_id();
-    collection.insert(id, entry).expect("Unable to update collection");
+    let stored: &T = match collection.get(&id) {
+        Some(stored) => stored,
+        None => return Err("Unable to update collection")
+    };
+    // keep the link to the card identity, if the provided object does not carry it. A link that is
+    // explicitly `null` is removed.
+    if get_upstream_id_update(obj).is_none() {
+        entry.set_upstream_id(stored.get_upstream_id().map(String::from));
+    }
+    // images that were added or removed change their references only now, so cancelling an edit keeps them
+    let added: Vec<String> = entry.get_images().iter().filter(|image| !stored.get_images().contains(image)).cloned().collect();
+    let removed: Vec<String> = stored.get_images().iter().filter(|image| !entry.get_images().contains(image)).cloned().collect();
+    for image in added.iter() {
+        add_image_reference(game, image)?;
+    }
+    for image in removed.iter() {
+        delete_image(game, image)?;
+    }
+    retain_image_metadata(&mut entry);
+    rename_entry_images(game, &mut entry, id, &mut ImageRenameReport::default());
+    collection.insert(id, entry.clone());
     store_collection::<T>(game, &collection).expect("Unable to store updated collection.");
-    Ok(())
+    Ok(entry)
+}
+
+/// Get the link to the card identity of the provided entry object, to tell an omitted link (`None`)
+/// from a removed one (`Some(None)`).
+fn get_upstream_id_update(obj: &str) -> Option<Option<String>> {
+    let entry: Value = serde_json::from_str(obj).ok()?;
+    entry.get("upstreamId").map(|upstream_id| upstream_id.as_str().map(String::from))
 }
+
 /// Return the entry with the specfied `id` from the collection corresponding to the specified `game`.
 /// 
 /// # Arguments
@@ -54,7 +222,7 @@ pub fn update_entry_in_collection<'a, T: Serialize + DeserializeOwned + MapEntry
 /// # Returns
 /// Entry record with the specified id from the specified collection
 /// 
-pub fn get_entry_by_id<'a, T: Serialize + DeserializeOwned + Clone>(game: &str, id: &u32) -> Result<T, &'a str> {
+pub fn get_entry_by_id<'a, T: Serialize + DeserializeOwned + Clone + EntryWithSet>(game: &str, id: &u32) -> Result<T, &'a str> {
     let collection: HashMap<u32, T> = load_collection::<T>(game).unwrap();
     match collection.get(id) {
         Some(entry) => Ok(entry.clone()),
@@ -68,7 +236,7 @@ pub fn get_entry_by_id<'a, T: Serialize + DeserializeOwned + Clone>(game: &str,
 /// `game`  - Game name to specifiy from which collection the entry should be deleted
 /// `id`    - Id of the entry that should be deleted
 /// 
-pub fn delete_entry_by_id<'a, T: Serialize + DeserializeOwned + Clone>(game: &str, id: &u32) -> Result<(), &'a str> {
+pub fn delete_entry_by_id<'a, T: Serialize + DeserializeOwned + Clone + EntryWithSet>(game: &str, id: &u32) -> Result<(), &'a str> {
     let mut collection: HashMap<u32, T> = load_collection::<T>(game).unwrap();
     collection.remove(id).unwrap();
     store_collection::<T>(game, &collection)
@@ -83,7 +251,7 @@ pub fn delete_entry_by_id<'a, T: Serialize + DeserializeOwned + Clone>(game: &st
 /// # Returns
 /// string with a map of the collection data
 /// 
-pub fn get_collection_json<'a, T: Serialize + DeserializeOwned>(game: &'a str) -> Result<String, &'a str> {
+pub fn get_collection_json<'a, T: Serialize + DeserializeOwned + EntryWithSet>(game: &'a str) -> Result<String, &'a str> {
     let collection = load_collection::<T>(game).unwrap();
     match serde_json::to_string(&collection) {
         Ok(json) => Ok(json),
@@ -91,8 +259,183 @@ pub fn get_collection_json<'a, T: Serialize + DeserializeOwned>(game: &'a str) -
     }
 }
 
+/// Convert all entries of the collection related to the provided game, that still embed a full copy
+/// of their set, into entries that reference their set by id. Entries whose set cannot be found
+/// within the sets of the game keep their embedded set, so that no set data gets lost.
+/// 
+/// # Arguments
+/// `game`  - Game name to specify which collection should be migrated
+/// 
+pub fn migrate_set_references<'a, T: Serialize + DeserializeOwned + EntryWithSet>(game: &str) -> Result<(), &'a str> {
+    let collection: HashMap<u32, T> = load_collection::<T>(game)?;
+    store_collection::<T>(game, &collection)
+}
+
+/// Get a report of all entries of the collection related to the provided game, whose set id can
+/// neither be found within the fetched sets nor within the user-defined sets of the game.
+/// 
+/// # Arguments
+/// `game`  - Game name to specify which collection should be checked
+/// 
+/// # Returns
+/// string with a JSON list of `EntrySetReference` objects, sorted by entry id
+/// 
+pub fn get_set_reference_report_json<'a, T: EntryWithSet>(game: &str) -> Result<String, &'a str> {
+    let sets: Vec<T::Set> = load_sets::<T::Set>(game).unwrap_or_default();
+    let mut report: Vec<EntrySetReference> = get_set_references(game)?;
+    report.retain(|reference| !sets.iter().any(|set| set.get_id() == reference.set_id));
+
+    match serde_json::to_string(&report) {
+        Ok(json) => Ok(json),
+        Err(_) => Err("Unable to serialize set reference report to JSON.")
+    }
+}
+
+/// Get the set references of all entries of the collection related to the provided game.
+/// 
+/// # Arguments
+/// `game`  - Game name to specify which collection should be used
+/// 
+/// # Returns
+/// Vector of the set references of all entries, sorted by entry id
+/// 
+pub fn get_set_references<'a>(game: &str) -> Result<Vec<EntrySetReference>, &'a str> {
+    let entries: HashMap<u32, Value> = load_collection_values(game)?;
+
+    let mut references: Vec<EntrySetReference> = entries
+        .iter()
+        .map(|(id, entry)| EntrySetReference {
+            id: *id,
+            name: entry["name"].as_str().unwrap_or_default().to_string(),
+            set_id: match &entry["set"] {
+                Value::String(set_id) => set_id.clone(),
+                set => set["id"].as_str().unwrap_or_default().to_string()
+            }
+        })
+        .collect();
+    references.sort_by_key(|reference| reference.id);
+    Ok(references)
+}
+
+/// Link all entries of the collection of the provided game without card identity to the card of
+/// their set's catalog with the same name and collector number. Entries without collector number are
+/// linked, if their name is unique within the set's catalog. All other entries are left untouched
+/// and listed within the returned report for manual review.
+/// 
+/// # Arguments
+/// `game`      -   Game name to specify which collection should be resolved
+/// `fetch`     -   Function of the game that fetches the catalog of a set from the game's API
+/// `job`       -   Job that runs the resolution
+/// 
+/// # Returns
+/// Report of linked, ambiguous and unmatched entries
+/// 
+pub fn resolve_identities<'a, T: Serialize + DeserializeOwned + EntryWithSet + EntryWithIdentity>(game: &str, fetch: fn(&str, &Job) -> Result<Vec<CatalogCard>, &'a str>, job: &Job) -> Result<IdentityReport, &'a str> {
+    let collection: HashMap<u32, T> = load_collection::<T>(game)?;
+    let mut ids: Vec<u32> = collection.keys().cloned().collect();
+    ids.sort();
+
+    // card identities found for the entries, applied to the collection once all catalogs are fetched
+    let mut identities: HashMap<u32, String> = HashMap::new();
+    // catalogs are loaded once per set, sets without catalog are remembered as `None`
+    let mut catalogs: HashMap<String, Option<Vec<CatalogCard>>> = HashMap::new();
+    let mut report = IdentityReport::default();
+    for (index, id) in ids.iter().enumerate() {
+        job.check_cancelled()?;
+        job.report_progress(index as f32 / ids.len() as f32, "Resolving card identities");
+
+        let entry: &T = collection.get(id).unwrap();
+        if entry.get_upstream_id().is_some() {
+            report.already_linked += 1;
+            continue;
+        }
+
+        let set_id: String = entry.get_set_id().to_string();
+        let catalog = catalogs
+            .entry(set_id.clone())
+            .or_insert_with(|| get_catalog(game, &set_id, fetch, job).ok());
+        let catalog = match catalog {
+            Some(catalog) => catalog,
+            None => {
+                report.unmatched.push(get_unresolved_entry(*id, entry, "No card catalog available for the set.", Vec::new()));
+                continue;
+            }
+        };
+
+        match find_identity(entry.get_name(), entry.get_set_no(), catalog) {
+            Ok(card) => {
+                identities.insert(*id, card.id.clone());
+            },
+            Err((reason, candidates)) if candidates.is_empty() => {
+                report.unmatched.push(get_unresolved_entry(*id, entry, reason, candidates));
+            },
+            Err((reason, candidates)) => {
+                report.ambiguous.push(get_unresolved_entry(*id, entry, reason, candidates));
+            }
+        }
+    }
+
+    job.check_cancelled()?;
+    // the collection might have changed while the catalogs were fetched, so the identities are only
+    // applied to entries that still exist and are still not linked
+    let mut collection: HashMap<u32, T> = load_collection::<T>(game)?;
+    for (id, upstream_id) in identities.into_iter() {
+        if let Some(entry) = collection.get_mut(&id).filter(|entry| entry.get_upstream_id().is_none()) {
+            entry.set_upstream_id(Some(upstream_id));
+            report.resolved += 1;
+        }
+    }
+    store_collection::<T>(game, &collection)?;
+    Ok(report)
+}
+
+/// Find the card within the provided catalog that matches the provided name and collector number.
+/// 
+/// # Returns
+/// The matching card or the reason why there is no unique match, together with all candidates
+/// 
+fn find_identity<'a, 'c>(name: &str, set_no: &str, catalog: &'c Vec<CatalogCard>) -> Result<&'c CatalogCard, (&'a str, Vec<CatalogCard>)> {
+    let name = normalize_name(name);
+    // cards with several faces are named `<front face> // <back face>` and might be
+    // stored with the name of the front face only
+    let matches_name = |card: &CatalogCard| normalize_name(&card.name) == name
+        || card.name.split(" // ").any(|face| normalize_name(face) == name);
+    let named: Vec<&CatalogCard> = catalog.iter().filter(|card| matches_name(card)).collect();
+
+    if !set_no.trim().is_empty() {
+        let number = normalize_number(set_no);
+        let numbered: Vec<&CatalogCard> = catalog.iter().filter(|card| normalize_number(&card.number) == number).collect();
+        let matching: Vec<&CatalogCard> = numbered.iter().filter(|card| matches_name(card)).cloned().collect();
+        return match matching.len() {
+            1 => Ok(matching[0]),
+            0 if !numbered.is_empty() => Err(("Name and number match different cards.", numbered.into_iter().chain(named).cloned().collect())),
+            0 if !named.is_empty() => Err(("No card with this number, but with this name.", named.into_iter().cloned().collect())),
+            0 => Err(("No card with this name or number.", Vec::new())),
+            _ => Err(("Several cards match name and number.", matching.into_iter().cloned().collect()))
+        };
+    }
+
+    match named.len() {
+        1 => Ok(named[0]),
+        0 => Err(("No card with this name.", Vec::new())),
+        _ => Err(("Several cards match the name, the number is required.", named.into_iter().cloned().collect()))
+    }
+}
+
+fn get_unresolved_entry<T: EntryWithIdentity>(id: u32, entry: &T, reason: &str, candidates: Vec<CatalogCard>) -> UnresolvedEntry {
+    UnresolvedEntry {
+        id: id,
+        name: entry.get_name().to_string(),
+        set_id: entry.get_set_id().to_string(),
+        set_no: entry.get_set_no().to_string(),
+        reason: reason.to_string(),
+        candidates: candidates
+    }
+}
+
 /// Load the collection related to the provided game from the corresponding collection file and
-/// return it as a hash map.
+/// return it as a hash map. The set references of all entries are resolved against the sets
+/// of the game.
 /// 
 /// # Arguments
 /// `game`  - Game name to specify which collection should be loaded
@@ -100,7 +443,28 @@ pub fn get_collection_json<'a, T: Serialize + DeserializeOwned>(game: &'a str) -
 /// # Returns
 /// The collection corresponding to the game name as a hash map
 /// 
-fn load_collection<'a, T: Serialize + DeserializeOwned>(game: &str) -> Result<HashMap<u32, T>, &'a str> {
+fn load_collection<'a, T: Serialize + DeserializeOwned + EntryWithSet>(game: &str) -> Result<HashMap<u32, T>, &'a str> {
+    let sets: Vec<T::Set> = load_sets::<T::Set>(game).unwrap_or_default();
+    let entries: HashMap<u32, Value> = load_collection_values(game)?;
+
+    let mut collection: HashMap<u32, T> = HashMap::new();
+    for (id, mut entry) in entries.into_iter() {
+        resolve_set_reference::<T::Set>(&mut entry, &sets);
+        migrate_legacy_image_roles(&mut entry);
+        let entry: T = serde_json::from_value(entry).expect("Unable to deserialize collection.");
+        collection.insert(id, entry);
+    }
+    Ok(collection)
+}
+
+/// Load the collection related to the provided game from the corresponding collection file 
+/// as raw JSON values, without resolving any set references. If the collection file does not
+/// exist, it will be generated.
+/// 
+/// # Arguments
+/// `game`  - Game name to specify which collection should be loaded
+/// 
+fn load_collection_values<'a>(game: &str) -> Result<HashMap<u32, Value>, &'a str> {
 
     let config: Configuration = load_configuration().unwrap();
 
@@ -111,23 +475,43 @@ fn load_collection<'a, T: Serialize + DeserializeOwned>(game: &str) -> Result<Ha
         let mut data = String::new();
         let mut f = File::open(&collection_file_path).expect("Unable to open file stream.");
         f.read_to_string(&mut data).expect("Unable to read file to string.");
-        let collection: HashMap<u32, T> = serde_json::from_str(&data).expect("Unable to deserialize collection.");
+        let collection: HashMap<u32, Value> = serde_json::from_str(&data).expect("Unable to deserialize collection.");
         Ok(collection)
     }
     else {
-        let collection: HashMap<u32, T> = HashMap::new();
-        store_collection::<T>(game, &collection).unwrap();
+        let collection: HashMap<u32, Value> = HashMap::new();
+        write_collection_file(game, &collection).unwrap();
         Ok(collection)
     }
 }
 
 /// Store the provided collection to the corresponding file specified by the provided game name.
+/// The set of each entry is stored as a reference by id, if the set can be found within the sets 
+/// of the game. Otherwise, the set stays embedded within the entry.
 /// 
 /// # Arguments
 /// `game`          -   Game name to specify to which game the provided collection belongs
 /// `collection`    -   Collection that should be stored to the collection file corresponding to the provided game
 /// 
-fn store_collection<'a, T: Serialize>(game: &str, collection: &HashMap<u32, T>) -> Result<(), &'a str> {
+fn store_collection<'a, T: Serialize + EntryWithSet>(game: &str, collection: &HashMap<u32, T>) -> Result<(), &'a str> {
+    let sets: Vec<T::Set> = load_sets::<T::Set>(game).unwrap_or_default();
+
+    let mut entries: HashMap<u32, Value> = HashMap::new();
+    for (id, entry) in collection.iter() {
+        let mut entry: Value = serde_json::to_value(entry).expect("Unable to serialize collection to JSON.");
+        reference_set::<T::Set>(&mut entry, &sets);
+        entries.insert(*id, entry);
+    }
+    write_collection_file(game, &entries)
+}
+
+/// Write the provided raw collection to the collection file of the provided game.
+/// 
+/// # Arguments
+/// `game`          -   Game name to specify to which game the provided collection belongs
+/// `collection`    -   Collection as map of raw JSON values
+/// 
+fn write_collection_file<'a>(game: &str, collection: &HashMap<u32, Value>) -> Result<(), &'a str> {
     let config: Configuration = load_configuration().unwrap();
 
     let game_dir = format!("{}/{}", &config.data_storage, game);
@@ -146,35 +530,98 @@ fn store_collection<'a, T: Serialize>(game: &str, collection: &HashMap<u32, T>)
     Ok(())
 }
 
+/// Replace the field `set` of the provided raw entry with the matching set of the provided sets.
+/// The field can either hold a set id or an embedded set (legacy format). If no matching set
+/// can be found, an embedded set is kept as it is, and a set id is replaced by a placeholder set
+/// that uses the id as its name.
+/// 
+/// # Arguments
+/// `entry` -   Raw JSON value of a collection entry
+/// `sets`  -   All sets of the entry's game
+/// 
+fn resolve_set_reference<S: Serialize + SetEntry>(entry: &mut Value, sets: &Vec<S>) {
+    let set_id: String = match &entry["set"] {
+        Value::String(set_id) => set_id.clone(),
+        Value::Object(set) => match set.get("id").and_then(Value::as_str) {
+            Some(set_id) => set_id.to_string(),
+            None => return
+        },
+        _ => return
+    };
+
+    match sets.iter().find(|set| set.get_id() == set_id) {
+        Some(set) => entry["set"] = serde_json::to_value(set).expect("Unable to serialize set."),
+        None => {
+            if entry["set"].is_string() {
+                entry["set"] = serde_json::json!({"id": set_id, "name": set_id, "releaseDate": ""});
+            }
+        }
+    }
+}
+
+/// Replace the embedded set of the provided raw entry by its set id, if the set can be found
+/// within the provided sets.
+/// 
+/// # Arguments
+/// `entry` -   Raw JSON value of a collection entry
+/// `sets`  -   All sets of the entry's game
+/// 
+fn reference_set<S: SetEntry>(entry: &mut Value, sets: &Vec<S>) {
+    let set_id: String = match entry["set"]["id"].as_str() {
+        Some(set_id) => set_id.to_string(),
+        None => return
+    };
+    if sets.iter().any(|set| set.get_id() == set_id) {
+        entry["set"] = Value::String(set_id);
+    }
+}
+
 /// Copy the image from the location specified via `img_location`. The path of the copied file depends on the specified `game`,
-/// the string provided via `img_target_name` and the flag `new_entry`. `game` basically translates into the corresponding
-/// game sub-directory in the apps storage directory. `img_target_name` is an arbitrary string that is used as the name of the 
-/// copied file. If the flag `new_entry` is `true`, this function will fetch the next id of the game's collection and use it
-/// as a prefix of the copied file name. Otherwise, it will simply use `img_target_name` as the filename.
+/// the provided `entry` and the flag `new_entry`. `game` basically translates into the corresponding
+/// game sub-directory in the apps storage directory. The name of the copied file is built from the configured filename template
+/// with the values of `entry` and the next free index of its images, see `allocate_image_filename`. If the flag `new_entry` is `true`, this function will fetch the next id
+/// of the game's collection and use it as the id of the entry. If the normalization of images is enabled, the copy is rotated
+/// upright, stripped of its metadata, scaled down and transcoded as configured, see `normalize_image`. Files that cannot be
+/// decoded are copied as they are. The copy is referenced once the entry is saved, so copies of cancelled
+/// edits can be dropped with `discard_image`.
 /// 
 /// # Arguments
 /// `img_location`      -   Absolute path to the image that should be copied
-/// `img_target_name`   -   Name that should be used for the copy
+/// `entry`             -   Entry that the image belongs to
 /// `game`              -   Game name to specify to which game the image belongs
 /// `new_entry`         -   Flag to indicate if the image belogns to a new entry (`true`) or an existing one (`false`).
-///                         In case of a new entry, the next id of the game's collection will be used as a prefix for 
+///                         In case of a new entry, the next id of the game's collection will be used as the id within
 ///                         the new image name.
 /// 
 /// # Returns
 /// The name of the new image file.
 /// 
-pub fn copy_image<'a, T: Serialize + DeserializeOwned>(img_location: &str, img_target_name: &str, game: &str, new_entry: bool) -> Result<String, &'a str> {
+pub fn copy_image<'a, T: Serialize + DeserializeOwned + MapEntryWithId + EntryWithImages>(img_location: &str, entry: &T, game: &str, new_entry: bool) -> Result<String, &'a str> {
     let config = load_configuration().expect("Unable to load configuration");
-    let file_extension = Path::new(&img_location).extension().and_then(OsStr::to_str).unwrap();
+    let file_extension = Path::new(&img_location).extension().and_then(OsStr::to_str).unwrap_or_default();
 
-    let mut new_filename: String = format_text_for_fs(&format!("{}.{}",img_target_name, file_extension));
+    // decode and re-encode the image, if it should be normalized and is a supported image
+    let normalized: Option<(Vec<u8>, &str)> = match config.images.normalize {
+        true => normalize_image(Path::new(img_location), &config.images).ok(),
+        false => None
+    };
+    let file_extension: &str = normalized.as_ref().map_or(file_extension, |(_, extension)| extension);
 
-    if new_entry {
-        let collection: HashMap<u32, T> = load_collection::<T>(game).unwrap();
-        let id: u32 = get_next_id::<T>(&collection);
-        new_filename = format!("{}+{}", id, &new_filename);
+    // images of the same content are stored once and shared by all entries that use them
+    let hash: String = match &normalized {
+        Some((data, _)) => get_data_hash(data),
+        None => get_content_hash(Path::new(img_location))?
+    };
+    if let Some(image) = find_image_by_hash(game, &hash) {
+        return Ok(image);
     }
 
+    let id: u32 = match new_entry {
+        true => get_next_id::<T>(&load_collection::<T>(game).unwrap()),
+        false => entry.get_id()
+    };
+    let new_filename: String = allocate_image_filename(game, &config.images.filename_template, entry, id, file_extension);
+
     // check if image dir exists
     let image_dir = format!("{}/{}/images", &config.data_storage, game);
     let image_dir_path = Path::new(&image_dir);
@@ -183,36 +630,645 @@ pub fn copy_image<'a, T: Serialize + DeserializeOwned>(img_location: &str, img_t
     }
 
     let copy_target = format!("{}/{}", &image_dir, &new_filename);
-    copy(img_location, &copy_target).expect("Unable to copy file.");
+    match normalized {
+        Some((data, _)) => {
+            let mut f = File::create(&copy_target).expect("Unable to create image file.");
+            f.write_all(&data).expect("Unable to write image file.");
+            if config.images.keep_originals {
+                store_original(game, &new_filename, Path::new(img_location))?;
+            }
+        },
+        None => {
+            copy(img_location, &copy_target).expect("Unable to copy file.");
+        }
+    }
+    generate_thumbnails_in_background(game, &new_filename);
     Ok(new_filename)
 }
 
-/// Returns the image, specified by the provided `game` and `image` name as a base-64 encoded string.
+/// Get the filename for a new image of the provided entry. The index of the image follows the highest index of all
+/// existing images of the entry, so images that were named with another filename template or by card collection
+/// manager v1 (`IMG_FRONT`, `IMG_BACK`) are supported as well. Indices, whose filename is already used by an image
+/// of the entry or by any file within the image directory, are skipped. If the template does not contain the index,
+/// the filename is numbered instead.
 /// 
 /// # Arguments
-/// `game`  -   Game name to specify to which game the image belongs
-/// `image` -   Name of the image to returns
+/// `game`      -   Game name to specify to which game the image belongs
+/// `template`  -   Filename template of the new image
+/// `entry`     -   Entry that the image belongs to
+/// `id`        -   Id of the entry
+/// `extension` -   File extension of the new image
 /// 
 /// # Returns
-/// Specified image as base-64 encoded string.
+/// The filename of the new image
 /// 
-pub fn get_entry_image_b64<'a>(game: &str, image: &str) -> Result<String, &'a str> {
-    let config = load_configuration().expect("Unable to load configuration");
-    let file_extension = Path::new(image).extension().and_then(OsStr::to_str).unwrap();
-    let image_location = format!("{}/{}/images/{}", &config.data_storage, game, image); 
-    let img: DynamicImage = image::open(&image_location).unwrap();
-    let mut img_data: Vec<u8> = Vec::new();
-
-    let format = match file_extension {
-        "png" => ImageOutputFormat::Png,
-        "jpg" => ImageOutputFormat::Jpeg(255),
-        "jpeg" => ImageOutputFormat::Jpeg(255),
-        _ => ImageOutputFormat::Png,
+fn allocate_image_filename<T: EntryWithImages>(game: &str, template: &str, entry: &T, id: u32, extension: &str) -> String {
+    let images: &Vec<String> = entry.get_images();
+    let mut index: usize = images.iter()
+        .filter_map(|image| parse_index_from_filename(image))
+        .map(|index| index as usize + 1)
+        .max()
+        .unwrap_or(0);
+
+    let get_stem = |image: &str| Path::new(image).file_stem().and_then(OsStr::to_str).unwrap_or(image).to_string();
+    let stems: Vec<String> = images.iter().map(|image| get_stem(image)).collect();
+    let is_taken = |filename: &str| stems.contains(&get_stem(filename))
+        || get_entry_image_path(game, filename).map_or(false, |path| path.exists());
+    loop {
+        let filename: String = get_image_filename(template, entry, id, index, extension);
+        if !is_taken(&filename) {
+            return filename;
+        }
+        // the filename does not change with the index, if the template does not contain it
+        if filename == get_image_filename(template, entry, id, index + 1, extension) {
+            return get_unique_filename(&filename, is_taken);
+        }
+        index += 1;
+    }
+}
+
+/// Move the images of the entry with the provided id into the provided order. The provided images
+/// need to contain each image of the entry exactly once.
+/// 
+/// # Arguments
+/// `game`      -   Game name to specify to which collection the entry belongs
+/// `id`        -   Id of the entry
+/// `images`    -   Images of the entry in their new order
+/// 
+/// # Returns
+/// The updated entry
+/// 
+pub fn reorder_entry_images<'a, T: Serialize + DeserializeOwned + Clone + EntryWithImages>(game: &str, id: &u32, images: Vec<String>) -> Result<T, &'a str> {
+    update_entry_images::<T, _>(game, id, |entry| {
+        let mut current: Vec<String> = entry.get_images().clone();
+        let mut reordered: Vec<String> = images.clone();
+        current.sort();
+        reordered.sort();
+        if current != reordered {
+            return Err("The images do not match the images of the entry.");
+        }
+        entry.set_images(images);
+        Ok(())
+    })
+}
+
+/// Assign the provided role to an image of the entry with the provided id. If no role is provided,
+/// the role of the image is removed.
+/// 
+/// # Arguments
+/// `game`  -   Game name to specify to which collection the entry belongs
+/// `id`    -   Id of the entry
+/// `image` -   Name of the image
+/// `role`  -   New role of the image
+/// 
+/// # Returns
+/// The updated entry
+/// 
+pub fn set_entry_image_role<'a, T: Serialize + DeserializeOwned + Clone + EntryWithImages>(game: &str, id: &u32, image: &str, role: Option<ImageRole>) -> Result<T, &'a str> {
+    update_entry_images::<T, _>(game, id, |entry| {
+        if !entry.get_images().iter().any(|entry_image| entry_image == image) {
+            return Err("The image does not belong to the entry.");
+        }
+        let mut image_roles: HashMap<String, ImageRole> = entry.get_image_roles().clone();
+        match role {
+            Some(role) => image_roles.insert(image.to_string(), role),
+            None => image_roles.remove(image)
+        };
+        entry.set_image_roles(image_roles);
+        Ok(())
+    })
+}
+
+/// Designate an image of the entry with the provided id as its primary image, which is displayed
+/// first. If no image is provided, the entry has no designated primary image anymore.
+/// 
+/// # Arguments
+/// `game`  -   Game name to specify to which collection the entry belongs
+/// `id`    -   Id of the entry
+/// `image` -   Name of the primary image
+/// 
+/// # Returns
+/// The updated entry
+/// 
+pub fn set_entry_primary_image<'a, T: Serialize + DeserializeOwned + Clone + EntryWithImages>(game: &str, id: &u32, image: Option<&str>) -> Result<T, &'a str> {
+    update_entry_images::<T, _>(game, id, |entry| {
+        if let Some(image) = image {
+            if !entry.get_images().iter().any(|entry_image| entry_image == image) {
+                return Err("The image does not belong to the entry.");
+            }
+        }
+        entry.set_primary_image(image.map(String::from));
+        Ok(())
+    })
+}
+
+/// Apply the provided editing operation to an image of the entry with the provided id. Images that
+/// are shared with other entries are not changed, the entry gets its own copy of the image instead.
+/// 
+/// # Arguments
+/// `game`  -   Game name to specify to which collection the entry belongs
+/// `id`    -   Id of the entry, whose image should be edited
+/// `image` -   Name of the image
+/// `edit`  -   Operation that should be applied
+/// 
+/// # Returns
+/// The name of the edited image and the number of operations that can be undone
+/// 
+pub fn edit_entry_image<'a, T: Serialize + DeserializeOwned + EntryWithImages>(game: &str, id: &u32, image: &str, edit: &ImageEdit) -> Result<ImageEditResult, &'a str> {
+    let image: String = get_unshared_image::<T>(game, id, image)?;
+    let undo_count: u32 = edit_image(game, &image, edit)?;
+    Ok(ImageEditResult { image: image, undo_count: undo_count })
+}
+
+/// Undo the last editing operation of an image of the entry with the provided id. Images that are
+/// shared with other entries are not changed, the entry gets its own copy of the image instead.
+/// 
+/// # Arguments
+/// `game`  -   Game name to specify to which collection the entry belongs
+/// `id`    -   Id of the entry, whose image edit should be undone
+/// `image` -   Name of the image
+/// 
+/// # Returns
+/// The name of the restored image and the number of operations that can still be undone
+/// 
+pub fn undo_entry_image_edit<'a, T: Serialize + DeserializeOwned + EntryWithImages>(game: &str, id: &u32, image: &str) -> Result<ImageEditResult, &'a str> {
+    let image: String = get_unshared_image::<T>(game, id, image)?;
+    let undo_count: u32 = undo_image_edit(game, &image)?;
+    Ok(ImageEditResult { image: image, undo_count: undo_count })
+}
+
+/// Get the name of an image that only the entry with the provided id uses and that can therefore be changed.
+/// If the provided image is shared with other entries, it is copied with its edit history and the reference
+/// of the entry moves to the copy. Images that are not saved with the entry yet are copied without a reference,
+/// since the entry references them once it is saved.
+fn get_unshared_image<'a, T: Serialize + DeserializeOwned + EntryWithImages>(game: &str, id: &u32, image: &str) -> Result<String, &'a str> {
+    let source: PathBuf = get_entry_image_path(game, image)?;
+    let mut collection: HashMap<u32, T> = load_collection::<T>(game)?;
+    let entry: Option<&mut T> = collection.get_mut(id).filter(|entry| entry.get_images().iter().any(|entry_image| entry_image == image));
+    let own_references: u32 = if entry.is_some() { 1 } else { 0 };
+    if get_image_references(game, image) <= own_references {
+        return Ok(image.to_string());
+    }
+
+    let new_image: String = get_unique_filename(image, |filename| {
+        get_entry_image_path(game, filename).map_or(true, |path| path.exists())
+    });
+    if copy(&source, get_entry_image_path(game, &new_image)?).is_err() {
+        return Err("Unable to copy shared image.");
+    }
+    copy_image_history(game, image, &new_image);
+
+    if let Some(entry) = entry {
+        add_image_reference(game, &new_image)?;
+        release_image_reference(game, image)?;
+        let images: Vec<String> = entry.get_images().iter()
+            .map(|entry_image| if entry_image == image { new_image.clone() } else { entry_image.clone() })
+            .collect();
+        let mut image_roles: HashMap<String, ImageRole> = entry.get_image_roles().clone();
+        if let Some(role) = image_roles.remove(image) {
+            image_roles.insert(new_image.clone(), role);
+        }
+        if entry.get_primary_image() == Some(image) {
+            entry.set_primary_image(Some(new_image.clone()));
+        }
+        entry.set_images(images);
+        entry.set_image_roles(image_roles);
+        store_collection::<T>(game, &collection)?;
+    }
+    Ok(new_image)
+}
+
+/// Apply the provided change to the entry with the provided id and store the collection.
+fn update_entry_images<'a, T: Serialize + DeserializeOwned + Clone + EntryWithImages, F>(game: &str, id: &u32, change: F) -> Result<T, &'a str>
+    where F: FnOnce(&mut T) -> Result<(), &'a str> {
+    let mut collection: HashMap<u32, T> = load_collection::<T>(game)?;
+    let entry: &mut T = match collection.get_mut(id) {
+        Some(entry) => entry,
+        None => return Err("Unable to get entry")
     };
+    change(entry)?;
+    let entry: T = entry.clone();
+    store_collection::<T>(game, &collection)?;
+    Ok(entry)
+}
 
-    img.write_to(&mut Cursor::new(&mut img_data), format).unwrap();
-    let img_data_b64 = format!("data:image/{};base64,{}", file_extension, base64::encode(img_data));
-    Ok(img_data_b64)
+/// Remove the roles and the primary image designation of images that do not belong to the
+/// provided entry anymore, e.g. since they were deleted.
+fn retain_image_metadata<T: EntryWithImages>(entry: &mut T) {
+    let images: Vec<String> = entry.get_images().clone();
+    let mut image_roles: HashMap<String, ImageRole> = entry.get_image_roles().clone();
+    image_roles.retain(|image, _| images.contains(image));
+    entry.set_image_roles(image_roles);
+    if entry.get_primary_image().map_or(false, |primary_image| !images.iter().any(|image| image == primary_image)) {
+        entry.set_primary_image(None);
+    }
+}
+
+/// Assign the roles `Front` and `Back` to the images of the provided raw entry, that are named `IMG_FRONT`
+/// and `IMG_BACK` by card collection manager v1 and have no role yet.
+fn migrate_legacy_image_roles(entry: &mut Value) {
+    let images: Vec<String> = match entry.get("images").and_then(Value::as_array) {
+        Some(images) => images.iter().filter_map(Value::as_str).map(String::from).collect(),
+        None => return
+    };
+    let entry = match entry.as_object_mut() {
+        Some(entry) => entry,
+        None => return
+    };
+    let image_roles = entry.entry("imageRoles").or_insert_with(|| Value::Object(serde_json::Map::new()));
+    if let Some(image_roles) = image_roles.as_object_mut() {
+        for image in images.iter() {
+            let role = match image {
+                image if image.contains("IMG_FRONT") => ImageRole::Front,
+                image if image.contains("IMG_BACK") => ImageRole::Back,
+                _ => continue
+            };
+            if !image_roles.contains_key(image) {
+                image_roles.insert(image.clone(), serde_json::to_value(role).unwrap());
+            }
+        }
+    }
+}
+
+/// Check the images of the collection of the provided game against the files within the image directory
+/// of the game. Interrupted edits can leave images that no entry references and entries that reference
+/// images that do not exist anymore.
+/// 
+/// # Arguments
+/// `game`  -   Game name to specify which collection should be checked
+/// 
+/// # Returns
+/// Report of orphaned images and dangling image references
+/// 
+pub fn check_images<'a, T: Serialize + DeserializeOwned + EntryWithImages>(game: &str) -> Result<ImageCheckReport, &'a str> {
+    let collection: HashMap<u32, T> = load_collection::<T>(game)?;
+    let files: Vec<String> = get_image_files(game);
+    Ok(get_image_check_report(&collection, files))
+}
+
+/// Compare the images referenced by the provided collection with the provided image files and
+/// return the orphaned and dangling images.
+fn get_image_check_report<T: EntryWithImages>(collection: &HashMap<u32, T>, files: Vec<String>) -> ImageCheckReport {
+    let mut report = ImageCheckReport::default();
+    let mut ids: Vec<&u32> = collection.keys().collect();
+    ids.sort();
+    for id in ids.into_iter() {
+        for image in collection[id].get_images().iter() {
+            if !files.contains(image) {
+                report.dangling.push(DanglingImage { entry_id: *id, image: image.clone() });
+            }
+        }
+    }
+    for file in files.into_iter() {
+        if collection.values().any(|entry| entry.get_images().contains(&file)) {
+            continue;
+        }
+        // the default filename template starts with the id of the entry
+        let prefix: String = file.chars().take_while(|c| c.is_ascii_digit()).collect();
+        let suggested_entry_id: Option<u32> = prefix.parse::<u32>().ok().filter(|id| collection.contains_key(id));
+        report.orphans.push(OrphanedImage { image: file, suggested_entry_id: suggested_entry_id });
+    }
+    report
+}
+
+/// Repair the images of the collection of the provided game, see `check_images`. Orphaned images can
+/// either be attached to the entries their names suggest or be deleted. Dangling image references can be
+/// removed from their entries.
+/// 
+/// # Arguments
+/// `game`              -   Game name to specify which collection should be repaired
+/// `attach`            -   Orphaned images that should be attached to their suggested entries
+/// `delete`            -   Orphaned images that should be deleted
+/// `remove_dangling`   -   Flag to indicate whether dangling image references should be removed
+/// 
+/// # Returns
+/// Report of the remaining orphaned images and dangling image references
+/// 
+pub fn repair_images<'a, T: Serialize + DeserializeOwned + EntryWithImages>(game: &str, attach: &Vec<String>, delete: &Vec<String>, remove_dangling: bool) -> Result<ImageCheckReport, &'a str> {
+    let report: ImageCheckReport = check_images::<T>(game)?;
+    let mut collection: HashMap<u32, T> = load_collection::<T>(game)?;
+
+    for orphan in report.orphans.iter() {
+        if attach.contains(&orphan.image) {
+            if let Some(entry) = orphan.suggested_entry_id.and_then(|id| collection.get_mut(&id)) {
+                let mut images: Vec<String> = entry.get_images().clone();
+                images.push(orphan.image.clone());
+                entry.set_images(images);
+                add_image_reference(game, &orphan.image)?;
+            }
+        }
+        else if delete.contains(&orphan.image) {
+            delete_image(game, &orphan.image)?;
+        }
+    }
+    if remove_dangling {
+        for dangling in report.dangling.iter() {
+            if let Some(entry) = collection.get_mut(&dangling.entry_id) {
+                let images: Vec<String> = entry.get_images().iter().filter(|image| **image != dangling.image).cloned().collect();
+                entry.set_images(images);
+                retain_image_metadata(entry);
+                release_image_reference(game, &dangling.image)?;
+            }
+        }
+    }
+    store_collection::<T>(game, &collection)?;
+    check_images::<T>(game)
+}
+
+/// Merge images of the collection of the provided game that have the same content, so each content is
+/// stored only once and shared by all entries that use it. The roles and the primary image of an entry
+/// move to the kept image.
+/// 
+/// # Arguments
+/// `game`  -   Game name to specify which collection should be deduplicated
+/// `job`   -   Job that runs the deduplication
+/// 
+/// # Returns
+/// Report of the storage used by the images and the storage saved by sharing them
+/// 
+pub fn deduplicate_images<'a, T: Serialize + DeserializeOwned + EntryWithImages>(game: &str, job: &Job) -> Result<DeduplicationReport, &'a str> {
+    job.report_progress(0.0, "Hashing images");
+    let index: HashMap<String, StoredImage> = rebuild_image_index(game)?;
+    job.check_cancelled()?;
+
+    // the first image by name of each content is kept
+    let mut images: Vec<(&String, &StoredImage)> = index.iter().collect();
+    images.sort_by(|(image, _), (other, _)| image.cmp(other));
+    let mut kept: HashMap<&str, &str> = HashMap::new();
+    let mut duplicates: HashMap<String, String> = HashMap::new();
+    for (image, stored) in images.into_iter() {
+        match kept.get(stored.hash.as_str()) {
+            Some(kept_image) => { duplicates.insert(image.clone(), kept_image.to_string()); },
+            None => { kept.insert(&stored.hash, image); }
+        }
+    }
+
+    job.report_progress(0.5, "Merging images");
+    let mut collection: HashMap<u32, T> = load_collection::<T>(game)?;
+    for entry in collection.values_mut() {
+        if !entry.get_images().iter().any(|image| duplicates.contains_key(image)) {
+            continue;
+        }
+        let mut images: Vec<String> = Vec::new();
+        let mut image_roles: HashMap<String, ImageRole> = entry.get_image_roles().clone();
+        for image in entry.get_images().iter() {
+            let kept_image: &String = match duplicates.get(image) {
+                Some(kept_image) => kept_image,
+                None => {
+                    if !images.contains(image) {
+                        images.push(image.clone());
+                    }
+                    continue;
+                }
+            };
+            if !images.contains(kept_image) {
+                images.push(kept_image.clone());
+            }
+            if let Some(role) = image_roles.remove(image) {
+                image_roles.entry(kept_image.clone()).or_insert(role);
+            }
+        }
+        let primary_image: Option<String> = entry.get_primary_image()
+            .map(|image| duplicates.get(image).map_or(image, |kept_image| kept_image.as_str()).to_string());
+        entry.set_images(images);
+        entry.set_image_roles(image_roles);
+        entry.set_primary_image(primary_image);
+    }
+    store_collection::<T>(game, &collection)?;
+
+    for image in duplicates.keys() {
+        remove_image_files(game, image)?;
+    }
+    rebuild_image_index(game)?;
+    let mut report: DeduplicationReport = get_deduplication_report(game);
+    report.merged = duplicates.len() as u32;
+    Ok(report)
+}
+
+/// Count the references of the entries of the collection of the provided game to each image. The raw
+/// collection is used, so the images of any game can be counted.
+/// 
+/// # Arguments
+/// `game`  -   Game name to specify which collection should be counted
+/// 
+/// # Returns
+/// Map of image names to the number of entries that use them
+/// 
+pub fn get_image_reference_counts(game: &str) -> HashMap<String, u32> {
+    let mut counts: HashMap<String, u32> = HashMap::new();
+    for entry in load_collection_values(game).unwrap_or_default().values() {
+        let images: Vec<String> = entry.get("images")
+            .and_then(|images| serde_json::from_value(images.clone()).ok())
+            .unwrap_or_default();
+        for image in images.into_iter() {
+            *counts.entry(image).or_insert(0) += 1;
+        }
+    }
+    counts
+}
+
+/// Get the names of all files within the image directory of the provided game. Temporary files of images
+/// that are written or renamed at the moment are skipped.
+fn get_image_files(game: &str) -> Vec<String> {
+    let config: Configuration = load_configuration().unwrap();
+    let image_dir: PathBuf = Path::new(&config.data_storage).join(game).join("images");
+    let is_temporary = |path: &Path| path.extension().and_then(OsStr::to_str)
+        .map_or(false, |extension| TEMPORARY_IMAGE_EXTENSIONS.contains(&extension));
+    let mut files: Vec<String> = match read_dir(image_dir) {
+        Ok(entries) => entries.flatten()
+            .filter(|entry| entry.path().is_file() && !is_temporary(&entry.path()))
+            .filter_map(|entry| entry.file_name().to_str().map(String::from))
+            .collect(),
+        Err(_) => Vec::new()
+    };
+    files.sort();
+    files
+}
+
+/// Build the filename of an image from the provided filename template, see `ImageConfiguration`. Unknown
+/// placeholders are kept as they are. The filename is formatted to be usable within the file system.
+/// 
+/// # Arguments
+/// `template`  -   Filename template, e.g. `{id}+{setName}+{name}+{index}`
+/// `entry`     -   Entry that the image belongs to
+/// `id`        -   Id of the entry
+/// `index`     -   Index of the image within the images of the entry
+/// `extension` -   File extension of the image
+/// 
+/// # Returns
+/// The filename of the image
+/// 
+pub fn get_image_filename<T: EntryWithImages>(template: &str, entry: &T, id: u32, index: usize, extension: &str) -> String {
+    let set = entry.get_set();
+    let mut filename = String::new();
+    let mut rest: &str = template;
+    while let Some(start) = rest.find('{') {
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end,
+            None => break
+        };
+        filename.push_str(&rest[..start]);
+        let value: String = match &rest[start + 1..end] {
+            "id" => id.to_string(),
+            "setCode" => set.get_code().unwrap_or(set.get_id()).to_string(),
+            "setName" => set.get_name().to_string(),
+            "number" => entry.get_set_no().to_string(),
+            "name" => entry.get_name().to_string(),
+            "language" => format!("{:?}", entry.get_language()),
+            "index" => index.to_string(),
+            _ => rest[start..=end].to_string()
+        };
+        filename.push_str(&value);
+        rest = &rest[end + 1..];
+    }
+    filename.push_str(rest);
+    match extension.is_empty() {
+        true => format_text_for_fs(&filename),
+        false => format_text_for_fs(&format!("{}.{}", filename, extension))
+    }
+}
+
+/// Rename the images of all entries of the collection of the provided game according to the
+/// configured filename template.
+/// 
+/// # Arguments
+/// `game`  -   Game name to specify which collection should be renamed
+/// `job`   -   Job that runs the renaming
+/// 
+/// # Returns
+/// Report of renamed and unchanged images and of images that could not be renamed
+/// 
+pub fn rename_images<'a, T: Serialize + DeserializeOwned + EntryWithImages>(game: &str, job: &Job) -> Result<ImageRenameReport, &'a str> {
+    let mut collection: HashMap<u32, T> = load_collection::<T>(game)?;
+    let mut ids: Vec<u32> = collection.keys().cloned().collect();
+    ids.sort();
+
+    let mut report = ImageRenameReport::default();
+    for (index, id) in ids.iter().enumerate() {
+        // files are renamed immediately, so the collection is stored before the job stops
+        if job.is_cancelled() {
+            break;
+        }
+        job.report_progress(index as f32 / ids.len() as f32, "Renaming images");
+        rename_entry_images(game, collection.get_mut(id).unwrap(), *id, &mut report);
+    }
+
+    store_collection::<T>(game, &collection)?;
+    job.check_cancelled()?;
+    Ok(report)
+}
+
+/// Rename the images of the provided entry according to the configured filename template and
+/// update the image names of the entry. Images are renamed via temporary names, so images of the
+/// same entry can swap their names.
+fn rename_entry_images<T: EntryWithImages>(game: &str, entry: &mut T, id: u32, report: &mut ImageRenameReport) {
+    let config: Configuration = load_configuration().unwrap();
+    let mut images: Vec<String> = entry.get_images().clone();
+
+    // images that should be renamed, with their index and new name
+    let mut renames: Vec<(usize, String)> = Vec::new();
+    for (index, image) in images.iter().enumerate() {
+        // shared images keep their names, since other entries reference them as well
+        if get_image_references(game, image) > 1 {
+            report.unchanged += 1;
+            continue;
+        }
+        let extension = Path::new(image).extension().and_then(OsStr::to_str).unwrap_or_default();
+        let new_image: String = get_image_filename(&config.images.filename_template, entry, id, index, extension);
+        // files of other entries keep their names, so the image gets a numbered name instead
+        let new_image: String = get_unique_filename(&new_image, |filename| {
+            filename != image && !images.iter().any(|other| other == filename)
+                && get_entry_image_path(game, filename).map_or(false, |path| path.exists())
+        });
+        if new_image == *image {
+            report.unchanged += 1;
+            continue;
+        }
+        match get_entry_image_path(game, image) {
+            Ok(source) if source.exists() => renames.push((index, new_image)),
+            _ => report.failed.push(image.clone())
+        }
+    }
+    // two images of the entry must not end up with the same name
+    let mut new_images: Vec<String> = images.clone();
+    for (index, new_image) in renames.iter() {
+        new_images[*index] = new_image.clone();
+    }
+    renames.retain(|(index, new_image)| {
+        let duplicate = new_images.iter().filter(|image| *image == new_image).count() > 1;
+        if duplicate {
+            report.failed.push(images[*index].clone());
+        }
+        !duplicate
+    });
+
+    let mut moved: Vec<(usize, String)> = Vec::new();
+    let mut renamed: Vec<(String, String)> = Vec::new();
+    for (index, new_image) in renames.into_iter() {
+        let source: PathBuf = get_entry_image_path(game, &images[index]).unwrap();
+        let tmp: PathBuf = source.with_file_name(format!("{}.rename", new_image));
+        match rename(&source, &tmp) {
+            Ok(_) => moved.push((index, new_image)),
+            Err(_) => report.failed.push(images[index].clone())
+        }
+    }
+    for (index, new_image) in moved.into_iter() {
+        let source: PathBuf = get_entry_image_path(game, &images[index]).unwrap();
+        let tmp: PathBuf = source.with_file_name(format!("{}.rename", new_image));
+        let target: PathBuf = get_entry_image_path(game, &new_image).unwrap();
+        if rename(&tmp, &target).is_err() {
+            // restore the previous name, if the new one cannot be used
+            let _ = rename(&tmp, &source);
+            report.failed.push(images[index].clone());
+            continue;
+        }
+        // the image index must follow the file, since the references of shared images are counted there
+        if rename_indexed_image(game, &images[index], &new_image).is_err() {
+            let _ = rename(&target, &source);
+            report.failed.push(images[index].clone());
+            continue;
+        }
+        rename_image_files(game, &images[index], &new_image);
+        // thumbnails that are not moved are generated again on their next request
+        let _ = rename_thumbnails(game, &images[index], &new_image);
+        renamed.push((images[index].clone(), new_image.clone()));
+        images[index] = new_image;
+        report.renamed += 1;
+    }
+
+    // keep the roles and the primary image assigned to the renamed images
+    let mut image_roles: HashMap<String, ImageRole> = entry.get_image_roles().clone();
+    let mut primary_image: Option<String> = entry.get_primary_image().map(String::from);
+    let previous_roles: Vec<Option<ImageRole>> = renamed.iter().map(|(image, _)| image_roles.remove(image)).collect();
+    for ((image, new_image), role) in renamed.iter().zip(previous_roles.into_iter()) {
+        if let Some(role) = role {
+            image_roles.insert(new_image.clone(), role);
+        }
+        if primary_image.as_deref() == Some(image.as_str()) {
+            primary_image = Some(new_image.clone());
+        }
+    }
+    entry.set_images(images);
+    entry.set_image_roles(image_roles);
+    entry.set_primary_image(primary_image);
+}
+
+/// Returns the location of the image, specified by the provided `game` and `image` name. Image names
+/// that would point outside of the game's image directory are rejected.
+/// 
+/// # Arguments
+/// `game`  -   Game name to specify to which game the image belongs
+/// `image` -   Name of the image
+/// 
+/// # Returns
+/// Location of the specified image file
+/// 
+pub fn get_entry_image_path<'a>(game: &str, image: &str) -> Result<PathBuf, &'a str> {
+    if image.is_empty() || image.contains(['/', '\\']) || image == "." || image == ".." {
+        return Err("Invalid image name.");
+    }
+    let config = load_configuration().expect("Unable to load configuration");
+    Ok(Path::new(&config.data_storage).join(game).join("images").join(image))
 }
 
 /// Delete the image, specified by the provided `game` and `image` name.
@@ -222,9 +1278,220 @@ pub fn get_entry_image_b64<'a>(game: &str, image: &str) -> Result<String, &'a st
 /// `image` -   Name of the image to delete
 /// 
 pub fn delete_image<'a>(game: &str, image: &str) -> Result<(), &'a str> {
+    // shared images are only deleted, once no entry uses them anymore
+    if release_image_reference(game, image)? > 0 {
+        return Ok(());
+    }
+    remove_image_files(game, image)
+}
+
+/// Delete an image that was copied for an entry, but not saved with it, e.g. since creating or editing the
+/// entry was cancelled. Images that saved entries reference are kept.
+/// 
+/// # Arguments
+/// `game`  -   Game name to specify to which game the image belongs
+/// `image` -   Name of the image to discard
+/// 
+pub fn discard_image<'a>(game: &str, image: &str) -> Result<(), &'a str> {
+    if get_image_references(game, image) > 0 {
+        return Ok(());
+    }
+    remove_image_files(game, image)
+}
+
+/// Delete the provided image with its thumbnails, original and edit history, regardless of its references.
+fn remove_image_files<'a>(game: &str, image: &str) -> Result<(), &'a str> {
     let config = load_configuration().expect("Unable to load configuration");
     let image_location = format!("{}/{}/images/{}", &config.data_storage, game, image); 
     let image_location_path = Path::new(&image_location);
-    remove_file(image_location_path).expect("Unable to delete image.");
-    Ok(())
-}
\ No newline at end of file
+    // the image might be missing already, e.g. if a dangling reference is removed from an entry
+    if image_location_path.exists() {
+        remove_file(image_location_path).expect("Unable to delete image.");
+    }
+    remove_original(game, image);
+    remove_image_history(game, image);
+    remove_thumbnails(game, image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::magic::card_services::Card;
+    use crate::magic::set_services::Set;
+
+    fn get_set(id: &str, name: &str) -> Set {
+        serde_json::from_value(json!({"id": id, "name": name, "releaseDate": "1993/08/05"})).unwrap()
+    }
+
+    fn get_card(name: &str, set_no: &str) -> Card {
+        get_card_with_images(name, set_no, vec![])
+    }
+
+    fn get_card_with_images(name: &str, set_no: &str, images: Vec<&str>) -> Card {
+        serde_json::from_value(json!({
+            "id": 7, "amount": 1, "name": name, "set": {"id": "lea", "name": "Limited Edition Alpha", "releaseDate": "1993/08/05"},
+            "setNo": set_no, "note": "", "images": images, "language": "English", "condition": "Mint",
+            "foil": false, "signed": false, "altered": false
+        })).unwrap()
+    }
+
+    fn get_catalog_card(id: &str, name: &str, number: &str) -> CatalogCard {
+        CatalogCard { id: id.to_string(), name: name.to_string(), number: number.to_string(), rarity: None }
+    }
+
+    fn get_catalog() -> Vec<CatalogCard> {
+        vec![
+            get_catalog_card("1", "Lightning Bolt", "161"),
+            get_catalog_card("2", "Lightning Bolt", "162"),
+            get_catalog_card("3", "Giant Growth", "194"),
+            get_catalog_card("4", "Fire // Ice", "128"),
+            get_catalog_card("5", "Æther Vial", "91")
+        ]
+    }
+
+    #[test]
+    fn reference_set_replaces_known_sets_by_id() {
+        let sets: Vec<Set> = vec![get_set("lea", "Limited Edition Alpha")];
+        let mut entry: Value = json!({"name": "Lightning Bolt", "set": {"id": "lea", "name": "Limited Edition Alpha", "releaseDate": "1993/08/05"}});
+        reference_set(&mut entry, &sets);
+        assert_eq!(entry["set"], json!("lea"));
+
+        // unknown sets stay embedded, so their data is not lost
+        let mut entry: Value = json!({"name": "Lightning Bolt", "set": {"id": "xyz", "name": "Unknown", "releaseDate": ""}});
+        reference_set(&mut entry, &sets);
+        assert_eq!(entry["set"]["name"], json!("Unknown"));
+    }
+
+    #[test]
+    fn resolve_set_reference_embeds_known_sets() {
+        let sets: Vec<Set> = vec![get_set("lea", "Limited Edition Alpha")];
+        let mut entry: Value = json!({"name": "Lightning Bolt", "set": "lea"});
+        resolve_set_reference(&mut entry, &sets);
+        assert_eq!(entry["set"]["name"], json!("Limited Edition Alpha"));
+
+        // embedded sets of the legacy format are replaced by the current set data
+        let mut entry: Value = json!({"name": "Lightning Bolt", "set": {"id": "lea", "name": "Alpha", "releaseDate": ""}});
+        resolve_set_reference(&mut entry, &sets);
+        assert_eq!(entry["set"]["name"], json!("Limited Edition Alpha"));
+    }
+
+    #[test]
+    fn resolve_set_reference_keeps_unknown_sets() {
+        let sets: Vec<Set> = vec![get_set("lea", "Limited Edition Alpha")];
+        let mut entry: Value = json!({"name": "Lightning Bolt", "set": {"id": "xyz", "name": "Unknown", "releaseDate": "2000/01/01"}});
+        resolve_set_reference(&mut entry, &sets);
+        assert_eq!(entry["set"], json!({"id": "xyz", "name": "Unknown", "releaseDate": "2000/01/01"}));
+
+        let mut entry: Value = json!({"name": "Lightning Bolt", "set": "xyz"});
+        resolve_set_reference(&mut entry, &sets);
+        assert_eq!(entry["set"], json!({"id": "xyz", "name": "xyz", "releaseDate": ""}));
+    }
+
+    #[test]
+    fn find_identity_matches_name_and_number() {
+        let catalog: Vec<CatalogCard> = get_catalog();
+        assert_eq!(find_identity("Lightning Bolt", "162", &catalog).unwrap().id, "2");
+        // numbers are compared without leading zeros and set sizes
+        assert_eq!(find_identity("lightning bolt", "0161/295", &catalog).unwrap().id, "1");
+        // names are compared without accents and ligatures
+        assert_eq!(find_identity("Aether Vial", "", &catalog).unwrap().id, "5");
+    }
+
+    #[test]
+    fn find_identity_matches_faces_of_double_faced_cards() {
+        let catalog: Vec<CatalogCard> = get_catalog();
+        assert_eq!(find_identity("Fire // Ice", "", &catalog).unwrap().id, "4");
+        assert_eq!(find_identity("Fire", "", &catalog).unwrap().id, "4");
+        assert_eq!(find_identity("Ice", "128", &catalog).unwrap().id, "4");
+    }
+
+    #[test]
+    fn find_identity_requires_number_for_several_matches() {
+        let catalog: Vec<CatalogCard> = get_catalog();
+        let (reason, candidates) = find_identity("Lightning Bolt", "", &catalog).unwrap_err();
+        assert_eq!(reason, "Several cards match the name, the number is required.");
+        assert_eq!(candidates.iter().map(|card| card.id.as_str()).collect::<Vec<&str>>(), vec!["1", "2"]);
+    }
+
+    #[test]
+    fn find_identity_reports_name_and_number_of_different_cards() {
+        let catalog: Vec<CatalogCard> = get_catalog();
+        let (reason, candidates) = find_identity("Giant Growth", "161", &catalog).unwrap_err();
+        assert_eq!(reason, "Name and number match different cards.");
+        assert_eq!(candidates.iter().map(|card| card.id.as_str()).collect::<Vec<&str>>(), vec!["1", "3"]);
+    }
+
+    #[test]
+    fn find_identity_reports_missing_cards() {
+        let catalog: Vec<CatalogCard> = get_catalog();
+        let (reason, candidates) = find_identity("Giant Growth", "999", &catalog).unwrap_err();
+        assert_eq!(reason, "No card with this number, but with this name.");
+        assert_eq!(candidates.len(), 1);
+
+        let (reason, candidates) = find_identity("Black Lotus", "999", &catalog).unwrap_err();
+        assert_eq!(reason, "No card with this name or number.");
+        assert!(candidates.is_empty());
+
+        let (reason, _) = find_identity("Black Lotus", "", &catalog).unwrap_err();
+        assert_eq!(reason, "No card with this name.");
+    }
+
+    #[test]
+    fn get_image_filename_replaces_placeholders() {
+        let card: Card = get_card("Lightning Bolt", "161");
+        assert_eq!(get_image_filename("{id}+{setName}+{name}+{index}", &card, 7, 2, "jpg"), "7+LimitedEditionAlpha+LightningBolt+2.jpg");
+        assert_eq!(get_image_filename("{setCode}-{number}-{language}", &card, 7, 0, "png"), "lea-161-English.png");
+    }
+
+    #[test]
+    fn get_image_filename_uses_set_code_if_known() {
+        let card: crate::pokemon::card_services::Card = serde_json::from_value(json!({
+            "id": 3, "amount": 1, "name": "Charizard", "set": {"id": "base1", "name": "Base", "releaseDate": "1999/01/09", "ptcgoCode": "BS"},
+            "setNo": "4", "note": "", "images": [], "language": "English", "condition": "Mint",
+            "firstEdition": false, "holo": true, "signed": false, "altered": false
+        })).unwrap();
+        assert_eq!(get_image_filename("{setCode}-{number}", &card, 3, 0, "jpg"), "BS-4.jpg");
+    }
+
+    #[test]
+    fn get_image_filename_keeps_unknown_placeholders() {
+        let card: Card = get_card("Lightning Bolt", "161");
+        assert_eq!(get_image_filename("{id}-{rarity}", &card, 7, 0, "jpg"), "7-{rarity}.jpg");
+        // unterminated placeholders are kept as well
+        assert_eq!(get_image_filename("{id}-{name", &card, 7, 0, "jpg"), "7-{name.jpg");
+    }
+
+    #[test]
+    fn get_image_filename_formats_values_for_fs() {
+        let card: Card = get_card("Fire // Ice", "128/295");
+        assert_eq!(get_image_filename("{name}_{number}", &card, 7, 0, "jpg"), "Fire--Ice_128-295.jpg");
+        assert_eq!(get_image_filename("{id}_{index}", &card, 7, 1, ""), "7_1");
+    }
+
+    #[test]
+    fn image_check_report_lists_dangling_images_by_entry() {
+        let mut collection: HashMap<u32, Card> = HashMap::new();
+        collection.insert(12, get_card_with_images("Lightning Bolt", "161", vec!["12+bolt+0.jpg", "12+bolt+1.jpg"]));
+        collection.insert(3, get_card_with_images("Giant Growth", "194", vec!["3+growth+0.jpg"]));
+        let report: ImageCheckReport = get_image_check_report(&collection, vec![String::from("12+bolt+0.jpg")]);
+
+        assert!(report.orphans.is_empty());
+        let dangling: Vec<(u32, &str)> = report.dangling.iter().map(|image| (image.entry_id, image.image.as_str())).collect();
+        assert_eq!(dangling, vec![(3, "3+growth+0.jpg"), (12, "12+bolt+1.jpg")]);
+    }
+
+    #[test]
+    fn image_check_report_suggests_entries_of_orphans() {
+        let mut collection: HashMap<u32, Card> = HashMap::new();
+        collection.insert(12, get_card_with_images("Lightning Bolt", "161", vec!["12+bolt+0.jpg"]));
+        let files: Vec<String> = vec!["12+bolt+0.jpg", "12+bolt+1.jpg", "99+lotus+0.jpg", "scan.jpg"]
+            .into_iter().map(String::from).collect();
+        let report: ImageCheckReport = get_image_check_report(&collection, files);
+
+        assert!(report.dangling.is_empty());
+        let orphans: Vec<(&str, Option<u32>)> = report.orphans.iter().map(|image| (image.image.as_str(), image.suggested_entry_id)).collect();
+        // only ids of existing entries are suggested
+        assert_eq!(orphans, vec![("12+bolt+1.jpg", Some(12)), ("99+lotus+0.jpg", None), ("scan.jpg", None)]);
+    }
+}
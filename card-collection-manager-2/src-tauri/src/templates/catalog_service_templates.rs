@@ -0,0 +1,322 @@
+use std::io::{Read, Write};
+use std::path::Path;
+use std::fs::{File, create_dir_all, remove_file, rename};
+use std::sync::{Arc, Mutex, PoisonError};
+use serde::{Serialize, Deserialize};
+use serde::de::DeserializeOwned;
+
+use crate::util::config::{Configuration, load_configuration};
+use crate::util::jobs::Job;
+use crate::util::fs::fold_accent;
+use crate::templates::set_service_templates::{SetEntry, load_sets};
+
+/// Locks of the card catalogs by game and set, so that concurrent requests of a catalog that is not
+/// cached yet fetch it only once.
+static CATALOG_LOCKS: Mutex<Vec<(String, Arc<Mutex<()>>)>> = Mutex::new(Vec::new());
+
+/// Card of a set's card catalog, as provided by the game's API.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CatalogCard {
+    /// card id according to the game's API
+    pub id: String,
+    /// name of the card
+    pub name: String,
+    /// collector number of the card within its set
+    pub number: String,
+    /// rarity of the card, e.g. `common` or `Rare Holo`
+    pub rarity: Option<String>
+}
+
+/// Card of a set's card catalog that matches a catalog search or lookup.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CatalogMatch {
+    /// id of the set the card belongs to
+    #[serde(rename = "setId")]
+    pub set_id: String,
+    #[serde(flatten)]
+    pub card: CatalogCard
+}
+
+/// Get the card catalog of the set with the provided id. The catalog is loaded from the local 
+/// cache at `<storage_location>/<game>/catalog/<set>.json`. Only if it is not cached yet, it is
+/// fetched via the provided fetch function and stored in the cache. Concurrent requests of the same
+/// catalog wait for the running fetch instead of fetching the catalog again.
+/// 
+/// # Arguments
+/// `game`      -   Game name to specify to which game the set belongs
+/// `set_id`    -   Id of the set whose catalog should be returned
+/// `fetch`     -   Function of the game that fetches the catalog of a set from the game's API
+/// `job`       -   Job that runs the catalog fetch
+/// 
+/// # Returns
+/// All cards of the set
+/// 
+pub fn get_catalog<'a>(game: &str, set_id: &str, fetch: fn(&str, &Job) -> Result<Vec<CatalogCard>, &'a str>, job: &Job) -> Result<Vec<CatalogCard>, &'a str> {
+    if let Some(catalog) = load_catalog(game, set_id) {
+        return Ok(catalog);
+    }
+    let lock: Arc<Mutex<()>> = get_catalog_lock(game, set_id);
+    let _fetching = lock.lock().unwrap_or_else(PoisonError::into_inner);
+    // the catalog might have been fetched by a concurrent request in the meantime
+    match load_catalog(game, set_id) {
+        Some(catalog) => Ok(catalog),
+        None => fetch_catalog(game, set_id, fetch, job)
+    }
+}
+
+/// Fetch the card catalog of the set with the provided id via the provided fetch function 
+/// and store it in the local cache, replacing any previously cached catalog of the set.
+/// 
+/// # Arguments
+/// `game`      -   Game name to specify to which game the set belongs
+/// `set_id`    -   Id of the set whose catalog should be updated
+/// `fetch`     -   Function of the game that fetches the catalog of a set from the game's API
+/// `job`       -   Job that runs the catalog fetch
+/// 
+/// # Returns
+/// All cards of the set
+/// 
+pub fn update_catalog<'a>(game: &str, set_id: &str, fetch: fn(&str, &Job) -> Result<Vec<CatalogCard>, &'a str>, job: &Job) -> Result<Vec<CatalogCard>, &'a str> {
+    let lock: Arc<Mutex<()>> = get_catalog_lock(game, set_id);
+    let _fetching = lock.lock().unwrap_or_else(PoisonError::into_inner);
+    fetch_catalog(game, set_id, fetch, job)
+}
+
+fn fetch_catalog<'a>(game: &str, set_id: &str, fetch: fn(&str, &Job) -> Result<Vec<CatalogCard>, &'a str>, job: &Job) -> Result<Vec<CatalogCard>, &'a str> {
+    let catalog: Vec<CatalogCard> = fetch(set_id, job)?;
+    job.check_cancelled()?;
+    store_catalog(game, set_id, &catalog)?;
+    Ok(catalog)
+}
+
+/// Load the card catalog of the set with the provided id from the local cache.
+/// 
+/// # Arguments
+/// `game`      -   Game name to specify to which game the set belongs
+/// `set_id`    -   Id of the set whose catalog should be loaded
+/// 
+/// # Returns
+/// All cards of the set, if the catalog of the set is cached
+/// 
+pub fn load_catalog(game: &str, set_id: &str) -> Option<Vec<CatalogCard>> {
+    let catalog_file = get_catalog_file(game, set_id);
+    let catalog_file_path = Path::new(&catalog_file);
+
+    if catalog_file_path.exists() {
+        let mut data = String::new();
+        let mut f = File::open(&catalog_file_path).expect("Unable to open file stream.");
+        f.read_to_string(&mut data).expect("Unable to read file to string.");
+        Some(serde_json::from_str(&data).expect("Unable to deserialize catalog."))
+    }
+    else {
+        None
+    }
+}
+
+/// Store the provided card catalog of the set with the provided id in the local cache.
+/// 
+/// # Arguments
+/// `game`      -   Game name to specify to which game the set belongs
+/// `set_id`    -   Id of the set the catalog belongs to
+/// `catalog`   -   All cards of the set
+/// 
+pub fn store_catalog<'a>(game: &str, set_id: &str, catalog: &Vec<CatalogCard>) -> Result<(), &'a str> {
+    let config: Configuration = load_configuration().unwrap();
+    create_dir_all(format!("{}/{}/catalog", &config.data_storage, game)).unwrap();
+
+    let catalog_json = serde_json::to_string(catalog).expect("Unable to serialize catalog to JSON.");
+    // write to a temporary file first, so a catalog that is loaded concurrently is never incomplete
+    let catalog_file = get_catalog_file(game, set_id);
+    let tmp = format!("{}.tmp", &catalog_file);
+    let mut f = File::create(&tmp).expect("Unable to create catalog file.");
+    if f.write_all(&catalog_json.as_bytes()).is_err() || rename(&tmp, &catalog_file).is_err() {
+        let _ = remove_file(&tmp);
+        return Err("Unable to write JSON-serialized catalog to catalog file.");
+    }
+    Ok(())
+}
+
+/// Get the lock of the card catalog of the provided game and set.
+fn get_catalog_lock(game: &str, set_id: &str) -> Arc<Mutex<()>> {
+    let key = format!("{}/{}", game, set_id);
+    let mut locks = CATALOG_LOCKS.lock().unwrap_or_else(PoisonError::into_inner);
+    match locks.iter().find(|(lock_key, _)| *lock_key == key) {
+        Some((_, lock)) => lock.clone(),
+        None => {
+            let lock = Arc::new(Mutex::new(()));
+            locks.push((key, lock.clone()));
+            lock
+        }
+    }
+}
+
+fn get_catalog_file(game: &str, set_id: &str) -> String {
+    let config: Configuration = load_configuration().unwrap();
+    let filename: String = set_id.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' }).collect();
+    format!("{}/{}/catalog/{}.json", &config.data_storage, game, filename)
+}
+
+
+/// Search the card catalogs for cards whose name matches the provided query. The matching ignores
+/// case, accents and punctuation. Cards whose name equals the query are ranked first, followed by
+/// cards whose name or one of its words starts with the query, cards whose name contains the query
+/// and finally cards whose name is similar to the query, e.g. because of a typo.
+/// 
+/// If a set is provided, only the catalog of this set is searched and fetched if necessary. Otherwise
+/// all locally cached catalogs are searched.
+/// 
+/// # Arguments
+/// `game`      -   Game name to specify to which game the catalogs belong
+/// `query`     -   (Partial) card name to search for
+/// `set_id`    -   Id of the set whose catalog should be searched
+/// `limit`     -   Maximum number of returned matches
+/// `fetch`     -   Function of the game that fetches the catalog of a set from the game's API
+/// `job`       -   Job that runs the catalog fetch
+/// 
+/// # Returns
+/// Matching cards, best matches first
+/// 
+pub fn search_catalog<'a, T: DeserializeOwned + SetEntry>(game: &str, query: &str, set_id: Option<&str>, limit: usize, fetch: fn(&str, &Job) -> Result<Vec<CatalogCard>, &'a str>, job: &Job) -> Result<Vec<CatalogMatch>, &'a str> {
+    let query = normalize_name(query);
+    if query.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let catalogs: Vec<(String, Vec<CatalogCard>)> = match set_id {
+        Some(set_id) => vec![(set_id.to_string(), get_catalog(game, set_id, fetch, job)?)],
+        None => load_sets::<T>(game)?
+            .iter()
+            .filter_map(|set| load_catalog(game, set.get_id()).map(|catalog| (set.get_id().to_string(), catalog)))
+            .collect()
+    };
+
+    let mut matches: Vec<(usize, CatalogMatch)> = Vec::new();
+    for (set_id, catalog) in catalogs {
+        for card in catalog {
+            if let Some(score) = get_match_score(&query, &normalize_name(&card.name)) {
+                matches.push((score, CatalogMatch { set_id: set_id.clone(), card }));
+            }
+        }
+    }
+    matches.sort_by(|(score_a, a), (score_b, b)| score_a.cmp(score_b).then_with(|| a.card.name.cmp(&b.card.name)));
+    Ok(matches.into_iter().take(limit).map(|(_, catalog_match)| catalog_match).collect())
+}
+
+/// Look up the card with the provided collector number within the set with the provided id or code,
+/// e.g. `base1` and `4` or `DRI` and `4`. Set ids and codes are compared case-insensitive and leading
+/// zeros as well as the printed set size (`4/102`) of the number are ignored. The catalog of the set
+/// is fetched, if it is not cached yet.
+/// 
+/// # Arguments
+/// `game`      -   Game name to specify to which game the set belongs
+/// `set`       -   Id or code of the set
+/// `number`    -   Collector number of the card within the set
+/// `fetch`     -   Function of the game that fetches the catalog of a set from the game's API
+/// `job`       -   Job that runs the catalog fetch
+/// 
+/// # Returns
+/// The matching card, if there is one
+/// 
+pub fn lookup_card<'a, T: DeserializeOwned + SetEntry>(game: &str, set: &str, number: &str, fetch: fn(&str, &Job) -> Result<Vec<CatalogCard>, &'a str>, job: &Job) -> Result<Option<CatalogMatch>, &'a str> {
+    let set = set.trim();
+    let sets: Vec<T> = load_sets::<T>(game)?;
+    let set_id: String = match sets.iter()
+        .find(|entry| entry.get_id().eq_ignore_ascii_case(set))
+        .or_else(|| sets.iter().find(|entry| entry.get_code().map_or(false, |code| code.eq_ignore_ascii_case(set)))) {
+        Some(entry) => entry.get_id().to_string(),
+        None => return Ok(None)
+    };
+
+    let number = normalize_number(number);
+    let catalog = get_catalog(game, &set_id, fetch, job)?;
+    Ok(catalog
+        .into_iter()
+        .find(|card| normalize_number(&card.number) == number)
+        .map(|card| CatalogMatch { set_id, card }))
+}
+
+/// Normalize the provided card name for matching, i.e. lowercase it, remove accents and apostrophes, resolve
+/// ligatures and replace any other punctuation with single spaces.
+pub fn normalize_name(name: &str) -> String {
+    let mut normalized = String::new();
+    for c in name.chars().flat_map(char::to_lowercase) {
+        match fold_accent(c) {
+            // combining diacritical marks of decomposed characters
+            '\u{0300}'..='\u{036f}' | '\'' | '\u{2019}' => (),
+            // ligatures, e.g. within `Æther`
+            'æ' => normalized.push_str("ae"),
+            'œ' => normalized.push_str("oe"),
+            'ß' => normalized.push_str("ss"),
+            c if c.is_alphanumeric() => normalized.push(c),
+            _ => if !normalized.is_empty() && !normalized.ends_with(' ') {
+                normalized.push(' ');
+            }
+        }
+    }
+    normalized.trim_end().to_string()
+}
+
+/// Normalize the provided collector number for comparison, e.g. `004/102` becomes `4`.
+pub fn normalize_number(number: &str) -> String {
+    let number = number.split('/').next().unwrap_or("").trim().to_lowercase();
+    match number.trim_start_matches('0') {
+        "" => number,
+        trimmed => trimmed.to_string()
+    }
+}
+
+/// Get the rank of the provided normalized card name for the provided normalized query.
+/// Lower values are better matches, `None` means no match at all.
+fn get_match_score(query: &str, name: &str) -> Option<usize> {
+    if name == query {
+        return Some(0);
+    }
+    if name.starts_with(query) {
+        return Some(1);
+    }
+    if name.split(' ').any(|word| word.starts_with(query)) {
+        return Some(2);
+    }
+    if name.contains(query) {
+        return Some(3);
+    }
+
+    // allow one typo per four characters of the query, compared against the start of the name
+    // and the start of each of its words
+    let query: Vec<char> = query.chars().collect();
+    let max_distance = query.len() / 4;
+    if max_distance == 0 {
+        return None;
+    }
+    let query: &[char] = &query;
+    let name: Vec<char> = name.chars().collect();
+    let distance = (0..name.len())
+        .filter(|&start| start == 0 || name[start - 1] == ' ')
+        .flat_map(|start| {
+            let name = &name[start..];
+            (query.len() - 1..=query.len() + 1)
+                .filter(move |&len| len <= name.len())
+                .map(move |len| get_edit_distance(query, &name[..len]))
+        })
+        .min()?;
+    if distance <= max_distance {
+        Some(4 + distance)
+    }
+    else {
+        None
+    }
+}
+
+/// Get the Levenshtein distance between the two provided character sequences.
+fn get_edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, char_a) in a.iter().enumerate() {
+        let mut current: Vec<usize> = vec![i + 1; b.len() + 1];
+        for (j, char_b) in b.iter().enumerate() {
+            let substitution = previous[j] + if char_a == char_b { 0 } else { 1 };
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
@@ -0,0 +1,349 @@
+use std::io::{Read, Write};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::fs::{File, create_dir_all, remove_file, rename};
+use std::sync::Mutex;
+use serde::{Serialize, Deserialize};
+use sha2::{Sha256, Digest};
+
+use crate::util::config::{Configuration, load_configuration};
+use crate::templates::card_service_templates::{get_entry_image_path, get_image_reference_counts};
+use crate::templates::thumbnail_templates::get_content_hash;
+
+/// Lock for the image index of all games, since images are edited in the background while
+/// the frontend adds and deletes images as well.
+static INDEX_LOCK: Mutex<()> = Mutex::new(());
+
+/// Stored image, as listed within the image index at `<storage_location>/<game>/image_index.json`.
+/// Images of the same content are stored only once and shared by all entries that use them.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StoredImage {
+    /// SHA-256 hash of the content of the image
+    pub hash: String,
+    /// size of the image in bytes
+    pub size: u64,
+    /// number of references to the image by collection entries
+    pub references: u32
+}
+
+/// Storage used by the images of a collection and the storage saved by sharing images of the same content.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct DeduplicationReport {
+    /// number of stored images
+    pub images: u32,
+    /// number of references to the stored images
+    pub references: u32,
+    /// number of duplicate images that were merged into an image of the same content
+    pub merged: u32,
+    /// size of all stored images in bytes
+    #[serde(rename = "storedBytes")]
+    pub stored_bytes: u64,
+    /// size in bytes, that would be needed in addition, if every reference had its own copy of the image
+    #[serde(rename = "savedBytes")]
+    pub saved_bytes: u64
+}
+
+/// Find a stored image of the provided game with the provided content hash.
+///
+/// # Arguments
+/// `game`  -   Game name to specify to which game the image belongs
+/// `hash`  -   SHA-256 hash of the content, see `get_data_hash`
+///
+/// # Returns
+/// Name of the stored image, if an image with the provided content exists
+///
+pub fn find_image_by_hash(game: &str, hash: &str) -> Option<String> {
+    let _lock = INDEX_LOCK.lock().unwrap();
+    let index: HashMap<String, StoredImage> = load_image_index(game);
+    let mut images: Vec<&String> = index.iter()
+        .filter(|(_, stored)| stored.hash == hash)
+        .map(|(image, _)| image)
+        .filter(|image| get_entry_image_path(game, image).map_or(false, |path| path.exists()))
+        .collect();
+    images.sort();
+    images.first().map(|image| image.to_string())
+}
+
+/// Add a reference to the provided image. Images that are not indexed yet are added to the index
+/// with a single reference.
+///
+/// # Arguments
+/// `game`  -   Game name to specify to which game the image belongs
+/// `image` -   Name of the image
+///
+/// # Returns
+/// Number of references to the image
+///
+pub fn add_image_reference<'a>(game: &str, image: &str) -> Result<u32, &'a str> {
+    let _lock = INDEX_LOCK.lock().unwrap();
+    let mut index: HashMap<String, StoredImage> = load_image_index(game);
+    let references: u32 = add_reference(&mut index, image, || get_stored_image(game, image, 1))?;
+    store_image_index(game, &index)?;
+    Ok(references)
+}
+
+/// Release a reference to the provided image. The image is removed from the index, once no
+/// reference is left, so the caller can delete its file.
+///
+/// # Arguments
+/// `game`  -   Game name to specify to which game the image belongs
+/// `image` -   Name of the image
+///
+/// # Returns
+/// Number of remaining references to the image
+///
+pub fn release_image_reference<'a>(game: &str, image: &str) -> Result<u32, &'a str> {
+    let _lock = INDEX_LOCK.lock().unwrap();
+    let mut index: HashMap<String, StoredImage> = load_image_index(game);
+    let references: u32 = release_reference(&mut index, image);
+    store_image_index(game, &index)?;
+    Ok(references)
+}
+
+/// Get the number of references to the provided image. Images that are not indexed have no references.
+///
+/// # Arguments
+/// `game`  -   Game name to specify to which game the image belongs
+/// `image` -   Name of the image
+///
+pub fn get_image_references(game: &str, image: &str) -> u32 {
+    let _lock = INDEX_LOCK.lock().unwrap();
+    load_image_index(game).get(image).map_or(0, |stored| stored.references)
+}
+
+/// Move the provided image to its new name within the image index.
+///
+/// # Arguments
+/// `game`      -   Game name to specify to which game the image belongs
+/// `image`     -   Previous name of the image
+/// `new_image` -   New name of the image
+///
+pub fn rename_indexed_image<'a>(game: &str, image: &str, new_image: &str) -> Result<(), &'a str> {
+    let _lock = INDEX_LOCK.lock().unwrap();
+    let mut index: HashMap<String, StoredImage> = load_image_index(game);
+    if let Some(stored) = index.remove(image) {
+        index.insert(new_image.to_string(), stored);
+        store_image_index(game, &index)?;
+    }
+    Ok(())
+}
+
+/// Update the content hash and size of the provided image, after its content changed, e.g. by an edit.
+/// Shared images must not be changed, see `edit_entry_image`.
+///
+/// # Arguments
+/// `game`  -   Game name to specify to which game the image belongs
+/// `image` -   Name of the image
+///
+pub fn update_indexed_image<'a>(game: &str, image: &str) -> Result<(), &'a str> {
+    let _lock = INDEX_LOCK.lock().unwrap();
+    let mut index: HashMap<String, StoredImage> = load_image_index(game);
+    let references: u32 = index.get(image).map_or(1, |stored| stored.references);
+    index.insert(image.to_string(), get_stored_image(game, image, references)?);
+    store_image_index(game, &index)
+}
+
+/// Rebuild the image index of the provided game from the image references of its collection. Images that
+/// are not referenced by any entry are not indexed, see `check_images` for those.
+///
+/// # Arguments
+/// `game`  -   Game name to specify which image index should be rebuilt
+///
+/// # Returns
+/// The rebuilt image index, mapping image names to their stored images
+///
+pub fn rebuild_image_index<'a>(game: &str) -> Result<HashMap<String, StoredImage>, &'a str> {
+    let _lock = INDEX_LOCK.lock().unwrap();
+    let index: HashMap<String, StoredImage> = build_image_index(game);
+    store_image_index(game, &index)?;
+    Ok(index)
+}
+
+/// Get the storage used by the images of the provided game and the storage saved by sharing images.
+///
+/// # Arguments
+/// `game`  -   Game name to specify to which game the images belong
+///
+pub fn get_deduplication_report(game: &str) -> DeduplicationReport {
+    let _lock = INDEX_LOCK.lock().unwrap();
+    summarize_image_index(&load_image_index(game))
+}
+
+/// Summarize the storage used by the images of the provided image index. The number of merged
+/// images is left at zero.
+fn summarize_image_index(index: &HashMap<String, StoredImage>) -> DeduplicationReport {
+    let mut report = DeduplicationReport::default();
+    for stored in index.values() {
+        report.images += 1;
+        report.references += stored.references;
+        report.stored_bytes += stored.size;
+        report.saved_bytes += stored.size * stored.references.saturating_sub(1) as u64;
+    }
+    report
+}
+
+/// Get the SHA-256 hash of the provided content as hex string, matching the hash of a file with
+/// this content, see `get_content_hash`.
+pub fn get_data_hash(data: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(data);
+    format!("{:x}", hasher.finalize())
+}
+
+/// Add a reference to the provided image within the provided image index. Images that are not
+/// indexed yet are added via the provided function.
+/// 
+/// # Returns
+/// The number of references to the image after adding the reference
+/// 
+fn add_reference<'a, F>(index: &mut HashMap<String, StoredImage>, image: &str, get_stored: F) -> Result<u32, &'a str>
+where F: FnOnce() -> Result<StoredImage, &'a str> {
+    match index.get_mut(image) {
+        Some(stored) => {
+            stored.references += 1;
+            Ok(stored.references)
+        },
+        None => {
+            let stored: StoredImage = get_stored()?;
+            let references: u32 = stored.references;
+            index.insert(image.to_string(), stored);
+            Ok(references)
+        }
+    }
+}
+
+/// Release a reference to the provided image within the provided image index. Images without
+/// any references left are removed from the index.
+/// 
+/// # Returns
+/// The number of references to the image that are left
+/// 
+fn release_reference(index: &mut HashMap<String, StoredImage>, image: &str) -> u32 {
+    match index.get_mut(image) {
+        Some(stored) if stored.references > 1 => {
+            stored.references -= 1;
+            stored.references
+        },
+        _ => {
+            index.remove(image);
+            0
+        }
+    }
+}
+
+fn get_stored_image<'a>(game: &str, image: &str, references: u32) -> Result<StoredImage, &'a str> {
+    let path: PathBuf = get_entry_image_path(game, image)?;
+    let size: u64 = match path.metadata() {
+        Ok(metadata) if metadata.is_file() => metadata.len(),
+        _ => return Err("Unable to find image.")
+    };
+    Ok(StoredImage { hash: get_content_hash(&path)?, size: size, references: references })
+}
+
+/// Index all images that are referenced by the collection of the provided game. Referenced images
+/// that do not exist are skipped.
+fn build_image_index(game: &str) -> HashMap<String, StoredImage> {
+    let mut index: HashMap<String, StoredImage> = HashMap::new();
+    for (image, references) in get_image_reference_counts(game).into_iter() {
+        if let Ok(stored) = get_stored_image(game, &image, references) {
+            index.insert(image, stored);
+        }
+    }
+    index
+}
+
+fn get_image_index_path(game: &str) -> PathBuf {
+    let config: Configuration = load_configuration().unwrap();
+    Path::new(&config.data_storage).join(game).join("image_index.json")
+}
+
+/// Load the image index of the provided game. Collections, whose images were stored before the
+/// index existed, are indexed on first use. An index that cannot be read is rebuilt from the collection
+/// as well, since an empty index would let shared images be deleted.
+fn load_image_index(game: &str) -> HashMap<String, StoredImage> {
+    let mut data = String::new();
+    let index: Option<HashMap<String, StoredImage>> = match File::open(get_image_index_path(game)) {
+        Ok(mut f) => match f.read_to_string(&mut data) {
+            Ok(_) => serde_json::from_str(&data).ok(),
+            Err(_) => None
+        },
+        Err(_) => None
+    };
+    match index {
+        Some(index) => index,
+        None => {
+            let index: HashMap<String, StoredImage> = build_image_index(game);
+            let _ = store_image_index(game, &index);
+            index
+        }
+    }
+}
+
+fn store_image_index<'a>(game: &str, index: &HashMap<String, StoredImage>) -> Result<(), &'a str> {
+    let path: PathBuf = get_image_index_path(game);
+    if let Some(game_dir) = path.parent() {
+        create_dir_all(game_dir).unwrap();
+    }
+    let json = serde_json::to_string(index).expect("Unable to serialize image index to JSON.");
+    // write to a temporary file first, so an interrupted write never leaves an incomplete index
+    let tmp: PathBuf = path.with_extension("json.tmp");
+    let mut f = match File::create(&tmp) {
+        Ok(f) => f,
+        Err(_) => return Err("Unable to create image index.")
+    };
+    if f.write_all(json.as_bytes()).is_err() || f.sync_all().is_err() || rename(&tmp, &path).is_err() {
+        let _ = remove_file(&tmp);
+        return Err("Unable to write image index.");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_stored(hash: &str, size: u64) -> Result<StoredImage, &'static str> {
+        Ok(StoredImage { hash: hash.to_string(), size: size, references: 1 })
+    }
+
+    #[test]
+    fn add_reference_counts_references() {
+        let mut index: HashMap<String, StoredImage> = HashMap::new();
+        assert_eq!(add_reference(&mut index, "bolt.jpg", || get_stored("abc", 100)), Ok(1));
+        // indexed images are not read again
+        assert_eq!(add_reference(&mut index, "bolt.jpg", || Err("Unable to find image.")), Ok(2));
+        assert_eq!(index["bolt.jpg"], StoredImage { hash: String::from("abc"), size: 100, references: 2 });
+    }
+
+    #[test]
+    fn add_reference_keeps_index_if_image_is_missing() {
+        let mut index: HashMap<String, StoredImage> = HashMap::new();
+        assert_eq!(add_reference(&mut index, "bolt.jpg", || Err("Unable to find image.")), Err("Unable to find image."));
+        assert!(index.is_empty());
+    }
+
+    #[test]
+    fn release_reference_removes_unreferenced_images() {
+        let mut index: HashMap<String, StoredImage> = HashMap::new();
+        index.insert(String::from("bolt.jpg"), StoredImage { hash: String::from("abc"), size: 100, references: 2 });
+        assert_eq!(release_reference(&mut index, "bolt.jpg"), 1);
+        assert_eq!(index["bolt.jpg"].references, 1);
+        assert_eq!(release_reference(&mut index, "bolt.jpg"), 0);
+        assert!(!index.contains_key("bolt.jpg"));
+        // images that are not indexed have no references
+        assert_eq!(release_reference(&mut index, "bolt.jpg"), 0);
+    }
+
+    #[test]
+    fn summarize_image_index_counts_saved_bytes_of_shared_images() {
+        let mut index: HashMap<String, StoredImage> = HashMap::new();
+        index.insert(String::from("bolt.jpg"), StoredImage { hash: String::from("abc"), size: 100, references: 3 });
+        index.insert(String::from("growth.jpg"), StoredImage { hash: String::from("def"), size: 50, references: 1 });
+        let report: DeduplicationReport = summarize_image_index(&index);
+        assert_eq!(report.images, 2);
+        assert_eq!(report.references, 4);
+        assert_eq!(report.stored_bytes, 150);
+        assert_eq!(report.saved_bytes, 200);
+        assert_eq!(report.merged, 0);
+    }
+}
@@ -0,0 +1,593 @@
+use std::io::{BufReader, Cursor, Write};
+use std::path::{Path, PathBuf};
+use std::fs::{File, create_dir_all, read_dir, remove_dir_all, remove_file, copy, rename};
+use serde::{Serialize, Deserialize};
+use image::{DynamicImage, GenericImageView, ImageOutputFormat, Rgba, RgbaImage};
+use image::imageops::FilterType;
+
+use crate::util::config::{Configuration, ImageConfiguration, ImageFormat, load_configuration};
+use crate::templates::card_service_templates::get_entry_image_path;
+use crate::templates::thumbnail_templates::generate_thumbnails_in_background;
+use crate::templates::image_index_templates::update_indexed_image;
+
+/// Maximum difference of a color channel to the border color, for a pixel to count as part of the border
+const TRIM_TOLERANCE: i32 = 24;
+/// Minimum share of the pixels of a line that need to match the border color, for the line to be trimmed
+const TRIM_MIN_SHARE: f32 = 0.98;
+/// Maximum angle in degrees, by which a scan is straightened
+const DESKEW_MAX_ANGLE: f32 = 15.0;
+
+/// Editing operation on a stored collection image
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type")]
+pub enum ImageEdit {
+    RotateLeft,
+    RotateRight,
+    Rotate180,
+    FlipHorizontal,
+    FlipVertical,
+    /// Crop the image to the provided rectangle in pixels
+    Crop { x: u32, y: u32, width: u32, height: u32 },
+    /// Remove uniformly colored borders, e.g. the scanner bed around a card
+    AutoTrim,
+    /// Straighten an image that is slightly rotated
+    Deskew
+}
+
+/// Result of editing a stored collection image
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ImageEditResult {
+    /// name of the edited image, which differs from the requested image, if a shared image was copied
+    pub image: String,
+    /// number of operations that can be undone
+    #[serde(rename = "undoCount")]
+    pub undo_count: u32
+}
+
+/// Open the provided image and rotate or flip it according to its EXIF orientation, so it is
+/// displayed upright without the EXIF metadata.
+///
+/// # Arguments
+/// `path`  -   Location of the image
+///
+/// # Returns
+/// The decoded image in its upright orientation
+///
+pub fn open_oriented_image<'a>(path: &Path) -> Result<DynamicImage, &'a str> {
+    let img: DynamicImage = match image::open(path) {
+        Ok(img) => img,
+        Err(_) => return Err("Unable to decode image.")
+    };
+    Ok(apply_orientation(img, get_exif_orientation(path)))
+}
+
+/// Normalize the provided image according to the provided configuration. The image is rotated
+/// according to its EXIF orientation, scaled down to the configured maximum size and encoded with
+/// the configured format and quality. Since the image is encoded from its pixels only, all metadata
+/// of the original file is stripped.
+///
+/// # Arguments
+/// `source`    -   Location of the image that should be normalized
+/// `config`    -   Image configuration of the app
+///
+/// # Returns
+/// The encoded image and the file extension of its format
+///
+pub fn normalize_image<'a>(source: &Path, config: &ImageConfiguration) -> Result<(Vec<u8>, &'static str), &'a str> {
+    let img: DynamicImage = open_oriented_image(source)?;
+    let max_size: u32 = config.max_size;
+    let img: DynamicImage = if max_size > 0 && (img.width() > max_size || img.height() > max_size) {
+        img.resize(max_size, max_size, FilterType::Lanczos3)
+    }
+    else {
+        img
+    };
+    let data: Vec<u8> = encode_image(&img, config.format, config.quality)?;
+    Ok((data, get_format_extension(config.format)))
+}
+
+/// Encode the provided image with the provided format. JPEG images have no alpha channel, so
+/// transparent pixels lose their transparency.
+///
+/// # Arguments
+/// `img`       -   Image that should be encoded
+/// `format`    -   Format of the encoded image
+/// `quality`   -   Quality of JPEG and WebP images from `1` to `100`
+///
+pub fn encode_image<'a>(img: &DynamicImage, format: ImageFormat, quality: u8) -> Result<Vec<u8>, &'a str> {
+    let quality: u8 = quality.clamp(1, 100);
+    let mut data: Vec<u8> = Vec::new();
+    match format {
+        ImageFormat::Jpeg => {
+            let rgb = DynamicImage::ImageRgb8(img.to_rgb8());
+            if rgb.write_to(&mut Cursor::new(&mut data), ImageOutputFormat::Jpeg(quality)).is_err() {
+                return Err("Unable to encode image.");
+            }
+        },
+        ImageFormat::Png => {
+            if img.write_to(&mut Cursor::new(&mut data), ImageOutputFormat::Png).is_err() {
+                return Err("Unable to encode image.");
+            }
+        },
+        ImageFormat::Webp => {
+            let rgba = img.to_rgba8();
+            let encoded = webp::Encoder::from_rgba(rgba.as_raw(), rgba.width(), rgba.height()).encode(quality as f32);
+            data.extend_from_slice(&encoded);
+        }
+    }
+    Ok(data)
+}
+
+/// Get the file extension of the provided image format.
+pub fn get_format_extension(format: ImageFormat) -> &'static str {
+    match format {
+        ImageFormat::Jpeg => "jpg",
+        ImageFormat::Png => "png",
+        ImageFormat::Webp => "webp"
+    }
+}
+
+/// Keep a copy of the original file of a normalized image at `<storage_location>/<game>/originals`.
+/// The copy is named like the normalized image, but keeps the extension of the original file.
+///
+/// # Arguments
+/// `game`      -   Game name to specify to which game the image belongs
+/// `image`     -   Name of the normalized image
+/// `source`    -   Location of the original file
+///
+pub fn store_original<'a>(game: &str, image: &str, source: &Path) -> Result<(), &'a str> {
+    let originals_dir: PathBuf = get_originals_dir(game);
+    create_dir_all(&originals_dir).unwrap();
+    let stem = Path::new(image).file_stem().and_then(|stem| stem.to_str()).unwrap_or(image);
+    let filename = match source.extension().and_then(|extension| extension.to_str()) {
+        Some(extension) => format!("{}.{}", stem, extension),
+        None => stem.to_string()
+    };
+    match copy(source, originals_dir.join(filename)) {
+        Ok(_) => Ok(()),
+        Err(_) => Err("Unable to copy original image.")
+    }
+}
+
+/// Delete the kept original files of the provided image, if there are any.
+///
+/// # Arguments
+/// `game`  -   Game name to specify to which game the image belongs
+/// `image` -   Name of the normalized image
+///
+pub fn remove_original(game: &str, image: &str) {
+    let stem = Path::new(image).file_stem().and_then(|stem| stem.to_str()).unwrap_or(image);
+    if let Ok(entries) = read_dir(get_originals_dir(game)) {
+        for entry in entries.flatten() {
+            if entry.path().file_stem().and_then(|entry_stem| entry_stem.to_str()) == Some(stem) {
+                let _ = remove_file(entry.path());
+            }
+        }
+    }
+}
+
+/// Rename the kept original files and the previous versions of the provided image, so they
+/// stay assigned to the image after it was renamed.
+///
+/// # Arguments
+/// `game`      -   Game name to specify to which game the image belongs
+/// `image`     -   Previous name of the image
+/// `new_image` -   New name of the image
+///
+pub fn rename_image_files(game: &str, image: &str, new_image: &str) {
+    let stem = Path::new(image).file_stem().and_then(|stem| stem.to_str()).unwrap_or(image);
+    let new_stem = Path::new(new_image).file_stem().and_then(|stem| stem.to_str()).unwrap_or(new_image);
+    if let Ok(entries) = read_dir(get_originals_dir(game)) {
+        for entry in entries.flatten() {
+            let path: PathBuf = entry.path();
+            if path.file_stem().and_then(|entry_stem| entry_stem.to_str()) == Some(stem) {
+                let filename = match path.extension().and_then(|extension| extension.to_str()) {
+                    Some(extension) => format!("{}.{}", new_stem, extension),
+                    None => new_stem.to_string()
+                };
+                let _ = rename(&path, path.with_file_name(filename));
+            }
+        }
+    }
+    let history_dir: PathBuf = get_history_dir(game, image);
+    if history_dir.exists() {
+        let _ = rename(history_dir, get_history_dir(game, new_image));
+    }
+}
+
+/// Read the EXIF orientation of the provided image. Images without EXIF data are upright, i.e. have
+/// the orientation `1`.
+fn get_exif_orientation(path: &Path) -> u32 {
+    let f = match File::open(path) {
+        Ok(f) => f,
+        Err(_) => return 1
+    };
+    exif::Reader::new().read_from_container(&mut BufReader::new(f)).ok()
+        .and_then(|exif| exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY).and_then(|field| field.value.get_uint(0)))
+        .unwrap_or(1)
+}
+
+/// Rotate and flip the provided image according to the provided EXIF orientation (`1` to `8`).
+fn apply_orientation(img: DynamicImage, orientation: u32) -> DynamicImage {
+    match orientation {
+        2 => img.fliph(),
+        3 => img.rotate180(),
+        4 => img.flipv(),
+        5 => img.rotate90().fliph(),
+        6 => img.rotate90(),
+        7 => img.rotate270().fliph(),
+        8 => img.rotate270(),
+        _ => img
+    }
+}
+
+/// Apply the provided editing operation to a stored collection image. The previous version of the image
+/// is kept at `<storage_location>/<game>/history/<image>`, so the operation can be undone.
+///
+/// # Arguments
+/// `game`  -   Game name to specify to which game the image belongs
+/// `image` -   Name of the image
+/// `edit`  -   Operation that should be applied
+///
+/// # Returns
+/// The number of operations that can be undone
+///
+pub fn edit_image<'a>(game: &str, image: &str, edit: &ImageEdit) -> Result<u32, &'a str> {
+    let path: PathBuf = get_entry_image_path(game, image)?;
+    let img: DynamicImage = open_oriented_image(&path)?;
+    let edited: DynamicImage = apply_edit(img, edit)?;
+
+    let history_dir: PathBuf = get_history_dir(game, image);
+    let next: u32 = get_history(&history_dir).last().map_or(0, |last| last + 1);
+    create_dir_all(&history_dir).unwrap();
+    if copy(&path, history_dir.join(next.to_string())).is_err() {
+        return Err("Unable to keep the previous version of the image.");
+    }
+    let config: Configuration = load_configuration().unwrap();
+    write_image(&edited, &path, config.images.quality)?;
+    update_indexed_image(game, image)?;
+    generate_thumbnails_in_background(game, image);
+    Ok(next + 1)
+}
+
+/// Undo the last editing operation of a stored collection image by restoring its previous version.
+///
+/// # Arguments
+/// `game`  -   Game name to specify to which game the image belongs
+/// `image` -   Name of the image
+///
+/// # Returns
+/// The number of operations that can still be undone
+///
+pub fn undo_image_edit<'a>(game: &str, image: &str) -> Result<u32, &'a str> {
+    let path: PathBuf = get_entry_image_path(game, image)?;
+    let history_dir: PathBuf = get_history_dir(game, image);
+    let last: u32 = match get_history(&history_dir).last() {
+        Some(last) => *last,
+        None => return Err("No edit of the image to undo.")
+    };
+    // copy instead of moving the previous version, so the restored image gets a new modification time
+    let previous: PathBuf = history_dir.join(last.to_string());
+    let tmp: PathBuf = path.with_extension("tmp");
+    if copy(&previous, &tmp).is_err() || rename(&tmp, &path).is_err() {
+        let _ = remove_file(&tmp);
+        return Err("Unable to restore the previous version of the image.");
+    }
+    let _ = remove_file(previous);
+    update_indexed_image(game, image)?;
+    generate_thumbnails_in_background(game, image);
+    Ok(last)
+}
+
+/// Copy all previous versions of the provided image, that were kept to undo editing operations, to
+/// the history of another image, e.g. a copy of the image.
+///
+/// # Arguments
+/// `game`      -   Game name to specify to which game the image belongs
+/// `image`     -   Name of the image whose history should be copied
+/// `new_image` -   Name of the image that gets the copied history
+///
+pub fn copy_image_history(game: &str, image: &str, new_image: &str) {
+    let history_dir: PathBuf = get_history_dir(game, image);
+    let new_history_dir: PathBuf = get_history_dir(game, new_image);
+    for version in get_history(&history_dir).into_iter() {
+        let _ = create_dir_all(&new_history_dir);
+        let _ = copy(history_dir.join(version.to_string()), new_history_dir.join(version.to_string()));
+    }
+}
+
+/// Delete all previous versions of the provided image, that were kept to undo editing operations.
+///
+/// # Arguments
+/// `game`  -   Game name to specify to which game the image belongs
+/// `image` -   Name of the image
+///
+pub fn remove_image_history(game: &str, image: &str) {
+    let history_dir: PathBuf = get_history_dir(game, image);
+    if history_dir.exists() {
+        let _ = remove_dir_all(history_dir);
+    }
+}
+
+fn apply_edit<'a>(img: DynamicImage, edit: &ImageEdit) -> Result<DynamicImage, &'a str> {
+    match edit {
+        ImageEdit::RotateLeft => Ok(img.rotate270()),
+        ImageEdit::RotateRight => Ok(img.rotate90()),
+        ImageEdit::Rotate180 => Ok(img.rotate180()),
+        ImageEdit::FlipHorizontal => Ok(img.fliph()),
+        ImageEdit::FlipVertical => Ok(img.flipv()),
+        ImageEdit::Crop { x, y, width, height } => {
+            if *width == 0 || *height == 0
+                || x.checked_add(*width).map_or(true, |right| right > img.width())
+                || y.checked_add(*height).map_or(true, |bottom| bottom > img.height()) {
+                return Err("Crop area exceeds the image.");
+            }
+            Ok(img.crop_imm(*x, *y, *width, *height))
+        },
+        ImageEdit::AutoTrim => Ok(auto_trim(img)),
+        ImageEdit::Deskew => {
+            let angle: f32 = get_skew_angle(&img);
+            if angle.abs() < 0.05 {
+                return Ok(img);
+            }
+            let fill: Rgba<u8> = get_corner_color(&img);
+            Ok(rotate_image(&img, angle, fill))
+        }
+    }
+}
+
+/// Remove the borders of the provided image. Starting at each edge, lines are removed as long as
+/// nearly all of their pixels match the color of the outermost line of this edge.
+fn auto_trim(img: DynamicImage) -> DynamicImage {
+    let rgba: RgbaImage = img.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    if width < 3 || height < 3 {
+        return img;
+    }
+    let row = |y: u32, from: u32, to: u32| (from..to).map(|x| *rgba.get_pixel(x, y)).collect::<Vec<Rgba<u8>>>();
+    let column = |x: u32, from: u32, to: u32| (from..to).map(|y| *rgba.get_pixel(x, y)).collect::<Vec<Rgba<u8>>>();
+
+    let top_color = get_mean_color(&row(0, 0, width));
+    let mut top: u32 = 0;
+    while top < height - 1 && is_border_line(&row(top, 0, width), &top_color) {
+        top += 1;
+    }
+    let bottom_color = get_mean_color(&row(height - 1, 0, width));
+    let mut bottom: u32 = height;
+    while bottom > top + 1 && is_border_line(&row(bottom - 1, 0, width), &bottom_color) {
+        bottom -= 1;
+    }
+    let left_color = get_mean_color(&column(0, top, bottom));
+    let mut left: u32 = 0;
+    while left < width - 1 && is_border_line(&column(left, top, bottom), &left_color) {
+        left += 1;
+    }
+    let right_color = get_mean_color(&column(width - 1, top, bottom));
+    let mut right: u32 = width;
+    while right > left + 1 && is_border_line(&column(right - 1, top, bottom), &right_color) {
+        right -= 1;
+    }
+
+    // an image that consists of its border only is not trimmed
+    if bottom <= top + 1 || right <= left + 1 {
+        return img;
+    }
+    img.crop_imm(left, top, right - left, bottom - top)
+}
+
+fn is_border_line(line: &[Rgba<u8>], color: &Rgba<u8>) -> bool {
+    let matching = line.iter()
+        .filter(|pixel| (0..3).all(|channel| (pixel[channel] as i32 - color[channel] as i32).abs() <= TRIM_TOLERANCE))
+        .count();
+    matching as f32 >= line.len() as f32 * TRIM_MIN_SHARE
+}
+
+fn get_mean_color(pixels: &[Rgba<u8>]) -> Rgba<u8> {
+    let mut sum = [0u64; 4];
+    for pixel in pixels.iter() {
+        for channel in 0..4 {
+            sum[channel] += pixel[channel] as u64;
+        }
+    }
+    let len = pixels.len().max(1) as u64;
+    Rgba([(sum[0] / len) as u8, (sum[1] / len) as u8, (sum[2] / len) as u8, (sum[3] / len) as u8])
+}
+
+fn get_corner_color(img: &DynamicImage) -> Rgba<u8> {
+    let (width, height) = img.dimensions();
+    let corners = [(0, 0), (width - 1, 0), (0, height - 1), (width - 1, height - 1)];
+    get_mean_color(&corners.iter().map(|(x, y)| img.get_pixel(*x, *y)).collect::<Vec<Rgba<u8>>>())
+}
+
+/// Estimate the angle in degrees, by which the content of the provided image is rotated clockwise. The
+/// edges of the image are projected onto the axes of each candidate angle. The straight edges of a card
+/// produce the sharpest projection at the angle, by which the card is rotated.
+fn get_skew_angle(img: &DynamicImage) -> f32 {
+    let gray = img.thumbnail(400, 400).to_luma8();
+    let (width, height) = gray.dimensions();
+    if width < 3 || height < 3 {
+        return 0.0;
+    }
+
+    // edge points with the direction of their gradient, i.e. `true` for edges of horizontal lines
+    let mut edges: Vec<(f32, f32, bool)> = Vec::new();
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            let gx = gray.get_pixel(x + 1, y)[0] as i32 - gray.get_pixel(x - 1, y)[0] as i32;
+            let gy = gray.get_pixel(x, y + 1)[0] as i32 - gray.get_pixel(x, y - 1)[0] as i32;
+            if gx.abs().max(gy.abs()) > 40 {
+                edges.push((x as f32, y as f32, gy.abs() > gx.abs()));
+            }
+        }
+    }
+    if edges.is_empty() {
+        return 0.0;
+    }
+
+    let size = (width + height) as usize * 2;
+    let get_score = |angle: f32| -> f64 {
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let mut rows = vec![0u32; size];
+        let mut columns = vec![0u32; size];
+        for (x, y, horizontal) in edges.iter() {
+            if *horizontal {
+                rows[(y * cos - x * sin + size as f32 / 2.0).max(0.0) as usize % size] += 1;
+            }
+            else {
+                columns[(x * cos + y * sin + size as f32 / 2.0).max(0.0) as usize % size] += 1;
+            }
+        }
+        rows.iter().chain(columns.iter()).map(|count| (*count as f64).powi(2)).sum()
+    };
+
+    // coarse search over all angles, followed by a fine search around the best one
+    let search = |from: f32, to: f32, step: f32| -> f32 {
+        let mut best = (0.0, f64::MIN);
+        let mut angle = from;
+        while angle <= to + step / 2.0 {
+            let score = get_score(angle);
+            if score > best.1 || (score == best.1 && angle.abs() < f32::abs(best.0)) {
+                best = (angle, score);
+            }
+            angle += step;
+        }
+        best.0
+    };
+    let coarse: f32 = search(-DESKEW_MAX_ANGLE, DESKEW_MAX_ANGLE, 0.5);
+    search(coarse - 0.5, coarse + 0.5, 0.05)
+}
+
+/// Rotate the content of the provided image counterclockwise by the provided angle in degrees around
+/// its center. The size of the image is kept and uncovered areas are filled with the provided color.
+fn rotate_image(img: &DynamicImage, angle: f32, fill: Rgba<u8>) -> DynamicImage {
+    let source: RgbaImage = img.to_rgba8();
+    let (width, height) = source.dimensions();
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let (cx, cy) = ((width as f32 - 1.0) / 2.0, (height as f32 - 1.0) / 2.0);
+
+    let sample = |x: i64, y: i64| -> [f32; 4] {
+        let pixel = match x >= 0 && y >= 0 && x < width as i64 && y < height as i64 {
+            true => *source.get_pixel(x as u32, y as u32),
+            false => fill
+        };
+        [pixel[0] as f32, pixel[1] as f32, pixel[2] as f32, pixel[3] as f32]
+    };
+    let rotated = RgbaImage::from_fn(width, height, |x, y| {
+        // bilinear interpolation of the source position of the target pixel
+        let (dx, dy) = (x as f32 - cx, y as f32 - cy);
+        let sx = cx + dx * cos - dy * sin;
+        let sy = cy + dx * sin + dy * cos;
+        let (x0, y0) = (sx.floor() as i64, sy.floor() as i64);
+        let (fx, fy) = (sx - sx.floor(), sy - sy.floor());
+        let (p00, p10, p01, p11) = (sample(x0, y0), sample(x0 + 1, y0), sample(x0, y0 + 1), sample(x0 + 1, y0 + 1));
+        let mut pixel = [0u8; 4];
+        for channel in 0..4 {
+            let top = p00[channel] * (1.0 - fx) + p10[channel] * fx;
+            let bottom = p01[channel] * (1.0 - fx) + p11[channel] * fx;
+            pixel[channel] = (top * (1.0 - fy) + bottom * fy).round().clamp(0.0, 255.0) as u8;
+        }
+        Rgba(pixel)
+    });
+    DynamicImage::ImageRgba8(rotated)
+}
+
+/// Encode the provided image in the format of the provided location and write it to this location.
+/// The file is written to a temporary file first, so the image is never served incompletely.
+fn write_image<'a>(img: &DynamicImage, path: &Path, quality: u8) -> Result<(), &'a str> {
+    let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+    let data: Vec<u8> = match extension.to_lowercase().as_str() {
+        "jpg" | "jpeg" => encode_image(img, ImageFormat::Jpeg, quality)?,
+        "png" => encode_image(img, ImageFormat::Png, quality)?,
+        "webp" => encode_image(img, ImageFormat::Webp, quality)?,
+        _ => {
+            let format = match image::ImageFormat::from_path(path) {
+                Ok(format) => format,
+                Err(_) => return Err("Unsupported image format.")
+            };
+            let mut data: Vec<u8> = Vec::new();
+            if img.write_to(&mut Cursor::new(&mut data), ImageOutputFormat::from(format)).is_err() {
+                return Err("Unable to encode image.");
+            }
+            data
+        }
+    };
+    let tmp: PathBuf = path.with_extension("tmp");
+    let mut f = match File::create(&tmp) {
+        Ok(f) => f,
+        Err(_) => return Err("Unable to create image file.")
+    };
+    if f.write_all(&data).is_err() || rename(&tmp, path).is_err() {
+        let _ = remove_file(&tmp);
+        return Err("Unable to write image file.");
+    }
+    Ok(())
+}
+
+/// Get the indices of the kept previous versions within the provided history directory in ascending order.
+fn get_history(history_dir: &Path) -> Vec<u32> {
+    let mut history: Vec<u32> = match read_dir(history_dir) {
+        Ok(entries) => entries.flatten()
+            .filter_map(|entry| entry.file_name().to_str().and_then(|filename| filename.parse::<u32>().ok()))
+            .collect(),
+        Err(_) => Vec::new()
+    };
+    history.sort();
+    history
+}
+
+fn get_history_dir(game: &str, image: &str) -> PathBuf {
+    let config: Configuration = load_configuration().unwrap();
+    Path::new(&config.data_storage).join(game).join("history").join(image)
+}
+
+fn get_originals_dir(game: &str) -> PathBuf {
+    let config: Configuration = load_configuration().unwrap();
+    Path::new(&config.data_storage).join(game).join("originals")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Image of a red card with the provided size on a white scanner bed with the provided margins
+    fn get_scan(card_width: u32, card_height: u32, margins: (u32, u32, u32, u32)) -> DynamicImage {
+        let (top, right, bottom, left) = margins;
+        let scan = RgbaImage::from_fn(left + card_width + right, top + card_height + bottom, |x, y| {
+            match x >= left && x < left + card_width && y >= top && y < top + card_height {
+                true => Rgba([200, 30, 30, 255]),
+                false => Rgba([250, 250, 250, 255])
+            }
+        });
+        DynamicImage::ImageRgba8(scan)
+    }
+
+    #[test]
+    fn auto_trim_removes_border_on_each_edge() {
+        let trimmed: RgbaImage = auto_trim(get_scan(30, 40, (3, 7, 5, 11))).to_rgba8();
+        assert_eq!(trimmed.dimensions(), (30, 40));
+        assert!(trimmed.pixels().all(|pixel| *pixel == Rgba([200, 30, 30, 255])));
+    }
+
+    #[test]
+    fn auto_trim_keeps_image_without_border() {
+        let trimmed: DynamicImage = auto_trim(get_scan(30, 40, (0, 0, 0, 0)));
+        assert_eq!(trimmed.dimensions(), (30, 40));
+    }
+
+    #[test]
+    fn auto_trim_keeps_image_of_border_only() {
+        let blank = DynamicImage::ImageRgba8(RgbaImage::from_pixel(20, 20, Rgba([250, 250, 250, 255])));
+        assert_eq!(auto_trim(blank).dimensions(), (20, 20));
+    }
+
+    #[test]
+    fn crop_rejects_overflowing_area() {
+        let img: DynamicImage = get_scan(30, 40, (0, 0, 0, 0));
+        let crop = ImageEdit::Crop { x: 10, y: 0, width: u32::MAX, height: 10 };
+        assert!(apply_edit(img.clone(), &crop).is_err());
+        let crop = ImageEdit::Crop { x: 0, y: u32::MAX, width: 10, height: 2 };
+        assert!(apply_edit(img.clone(), &crop).is_err());
+        let crop = ImageEdit::Crop { x: 10, y: 10, width: 20, height: 30 };
+        assert_eq!(apply_edit(img, &crop).unwrap().dimensions(), (20, 30));
+    }
+}
@@ -1,2 +1,7 @@
 pub mod set_service_templates;
-pub mod card_service_templates;
\ No newline at end of file
+pub mod set_asset_templates;
+pub mod card_service_templates;
+pub mod catalog_service_templates;
+pub mod thumbnail_templates;
+pub mod image_templates;
+pub mod image_index_templates;
\ No newline at end of file
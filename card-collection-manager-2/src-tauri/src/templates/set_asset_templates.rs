@@ -0,0 +1,184 @@
+use std::io::{Read, Write};
+use std::collections::HashMap;
+use std::path::Path;
+use std::fs::{File, create_dir_all, remove_file};
+use serde::de::DeserializeOwned;
+
+use crate::util::config::{Configuration, load_configuration};
+use crate::util::enums::SetAsset;
+use crate::util::http;
+use crate::templates::set_service_templates::{SetEntry, load_sets};
+use crate::templates::image_index_templates::get_data_hash;
+
+/// Get the asset of the specified kind (symbol or logo) of the set with the provided id as a
+/// base-64 encoded string. The asset is downloaded once into `<storage_location>/<game>/set_assets/`
+/// and served from there afterwards, so it is available without network access. A cached asset is
+/// only downloaded again if the URL of the asset has changed in the meantime.
+/// 
+/// # Arguments
+/// `game`      -   Game name to specify to which game the set belongs
+/// `set_id`    -   Id of the set whose asset should be returned
+/// `asset`     -   Kind of the asset that should be returned
+/// 
+/// # Returns
+/// Set asset as base-64 encoded string.
+/// 
+pub fn get_set_asset_b64<'a, T: DeserializeOwned + SetEntry>(game: &str, set_id: &str, asset: &SetAsset) -> Result<String, &'a str> {
+    let sets: Vec<T> = load_sets::<T>(game)?;
+    let set: &T = match sets.iter().find(|set| set.get_id() == set_id) {
+        Some(set) => set,
+        None => return Err("Unable to find set.")
+    };
+    let url: &str = match get_asset_url::<T>(set, asset) {
+        Some(url) => url,
+        None => return Err("Set does not provide this asset.")
+    };
+
+    let asset_dir = get_asset_dir(game);
+    let asset_filename = get_asset_filename(set_id, asset, url);
+    let asset_location = format!("{}/{}", &asset_dir, &asset_filename);
+
+    // download the asset if it is not cached yet or if its url has changed
+    let mut manifest: HashMap<String, String> = load_asset_manifest(game);
+    if !Path::new(&asset_location).exists() || manifest.get(&asset_filename).map(String::as_str) != Some(url) {
+        let data: Vec<u8> = match http::get_bytes(url) {
+            Ok(data) => data,
+            Err(_) => return Err("Unable to download set asset.")
+        };
+        create_dir_all(&asset_dir).unwrap();
+        let mut asset_file = File::create(&asset_location).expect("Unable to create set asset file.");
+        asset_file.write_all(&data).expect("Unable to write set asset file.");
+        manifest.insert(asset_filename.clone(), url.to_string());
+        store_asset_manifest(game, &manifest);
+    }
+
+    let mut data: Vec<u8> = Vec::new();
+    let mut f = File::open(&asset_location).expect("Unable to open file stream.");
+    f.read_to_end(&mut data).expect("Unable to read set asset file.");
+    Ok(format!("data:{};base64,{}", get_mime_type(&asset_filename), base64::encode(data)))
+}
+
+/// Delete all cached set assets of the provided game, whose URL differs from the URL within
+/// the provided sets, so that they are downloaded again the next time they are requested.
+/// 
+/// # Arguments
+/// `game`  -   Game name to specify to which game the sets belong
+/// `sets`  -   Vector of all updated sets of the game, including the user-defined sets
+/// 
+pub fn remove_outdated_set_assets<'a, T: SetEntry>(game: &str, sets: &Vec<&T>) -> Result<(), &'a str> {
+    let mut manifest: HashMap<String, String> = load_asset_manifest(game);
+    if manifest.is_empty() {
+        return Ok(());
+    }
+
+    let mut current_assets: HashMap<String, String> = HashMap::new();
+    for set in sets.iter() {
+        for asset in [SetAsset::Symbol, SetAsset::Logo].iter() {
+            if let Some(url) = get_asset_url::<T>(set, asset) {
+                current_assets.insert(get_asset_filename(set.get_id(), asset, url), url.to_string());
+            }
+        }
+    }
+
+    let asset_dir = get_asset_dir(game);
+    manifest.retain(|filename, url| {
+        if current_assets.get(filename) == Some(url) {
+            return true;
+        }
+        let asset_location = format!("{}/{}", &asset_dir, filename);
+        if Path::new(&asset_location).exists() {
+            remove_file(&asset_location).expect("Unable to delete set asset.");
+        }
+        false
+    });
+    store_asset_manifest(game, &manifest);
+    Ok(())
+}
+
+fn get_asset_url<'b, T: SetEntry>(set: &'b T, asset: &SetAsset) -> Option<&'b str> {
+    match asset {
+        SetAsset::Symbol => set.get_symbol_url(),
+        SetAsset::Logo => set.get_logo_url()
+    }
+}
+
+fn get_asset_dir(game: &str) -> String {
+    let config: Configuration = load_configuration().unwrap();
+    format!("{}/{}/set_assets", &config.data_storage, game)
+}
+
+/// Build the filename of a cached set asset from the set id, the kind of the asset and 
+/// the file extension of the asset's URL. Since set ids are formatted for the file system, a short
+/// hash of the set id is added, so sets whose ids only differ in special characters, e.g. the
+/// user-defined sets `a-b` and `a_b`, do not share their assets.
+/// 
+/// Example:
+/// (`dmu`, Symbol, `https://svgs.scryfall.io/sets/dmu.svg?1661140800`) -> `dmu-310452bca2c3-symbol.svg`
+/// 
+fn get_asset_filename(set_id: &str, asset: &SetAsset, url: &str) -> String {
+    let path = url.split(|c| c == '?' || c == '#').next().unwrap_or_default();
+    let file_extension = match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => ext.to_lowercase(),
+        None => String::from("png")
+    };
+    let kind = match asset {
+        SetAsset::Symbol => "symbol",
+        SetAsset::Logo => "logo"
+    };
+    let id_hash: String = get_data_hash(set_id.as_bytes()).chars().take(12).collect();
+    format!("{}-{}-{}.{}", set_id.replace(|c: char| !c.is_ascii_alphanumeric(), "_"), id_hash, kind, file_extension)
+}
+
+fn get_mime_type(filename: &str) -> &'static str {
+    match Path::new(filename).extension().and_then(|ext| ext.to_str()) {
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        _ => "application/octet-stream"
+    }
+}
+
+/// Load the manifest of cached set assets of the provided game, which maps each cached file
+/// to the URL it was downloaded from.
+fn load_asset_manifest(game: &str) -> HashMap<String, String> {
+    let manifest_file = format!("{}/assets.json", get_asset_dir(game));
+    let manifest_file_path = Path::new(&manifest_file);
+
+    if manifest_file_path.exists() {
+        let mut data = String::new();
+        let mut f = File::open(&manifest_file_path).expect("Unable to open file stream.");
+        f.read_to_string(&mut data).expect("Unable to read file to string.");
+        serde_json::from_str(&data).unwrap_or_default()
+    }
+    else {
+        HashMap::new()
+    }
+}
+
+fn store_asset_manifest(game: &str, manifest: &HashMap<String, String>) {
+    let asset_dir = get_asset_dir(game);
+    create_dir_all(&asset_dir).unwrap();
+    let manifest_json = serde_json::to_string(manifest).expect("Unable to serialize set asset manifest to JSON.");
+    let mut manifest_file = File::create(format!("{}/assets.json", &asset_dir)).expect("Unable to create 'assets.json' file.");
+    manifest_file.write_all(manifest_json.as_bytes()).expect("Unable to write set asset manifest to 'assets.json'.");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_asset_filename_keeps_extension_and_kind() {
+        assert_eq!(get_asset_filename("dmu", &SetAsset::Symbol, "https://svgs.scryfall.io/sets/dmu.svg?1661140800"), "dmu-310452bca2c3-symbol.svg");
+        assert!(get_asset_filename("base1", &SetAsset::Logo, "https://images.pokemontcg.io/base1/logo").ends_with("-logo.png"));
+    }
+
+    #[test]
+    fn get_asset_filename_separates_ids_with_special_characters() {
+        let url = "https://example.org/symbol.svg";
+        assert_ne!(get_asset_filename("a-b", &SetAsset::Symbol, url), get_asset_filename("a_b", &SetAsset::Symbol, url));
+        assert_ne!(get_asset_filename("a b", &SetAsset::Symbol, url), get_asset_filename("a-b", &SetAsset::Symbol, url));
+    }
+}
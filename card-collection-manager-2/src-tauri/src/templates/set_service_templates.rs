@@ -1,19 +1,137 @@
 use std::io::{Read, Write};
 use std::path::Path;
 use std::fs::{File, create_dir};
-use serde::{Serialize};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::atomic::{AtomicBool, Ordering};
+use serde::{Serialize, Deserialize};
+use serde::de::DeserializeOwned;
+use reqwest::StatusCode;
 use crate::util::config::{Configuration, load_configuration};
+use crate::util::http;
+use crate::templates::set_asset_templates;
+use crate::templates::card_service_templates::{EntrySetReference, get_set_references};
+
+/// Trait that a set type needs to implement in order to be merged with
+/// user-defined custom sets by the functions of this template.
+/// 
+pub trait SetEntry {
+    fn get_id(&self) -> &str;
+    fn get_name(&self) -> &str;
+    fn get_release_date(&self) -> &str;
+    fn get_code(&self) -> Option<&str>;
+    fn set_custom(&mut self, custom: bool);
+    fn get_symbol_url(&self) -> Option<&str>;
+    fn get_logo_url(&self) -> Option<&str>;
+    fn is_selectable(&self, config: &Configuration) -> bool;
+}
+
+/// Metadata of the last set fetch of a game, stored next to the game's sets.json file.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct SetFetchMetadata {
+    /// point in time of the last successful fetch in seconds since the unix epoch
+    #[serde(rename = "fetchedAt")]
+    pub fetched_at: u64,
+    /// `ETag` header value of the last response
+    pub etag: Option<String>,
+    /// `Last-Modified` header value of the last response
+    #[serde(rename = "lastModified")]
+    pub last_modified: Option<String>
+}
+
+/// Short description of a set within a set update report.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetSummary {
+    pub id: String,
+    pub name: String,
+    #[serde(rename = "releaseDate")]
+    pub release_date: String
+}
+
+/// Change of a single property of a set within a set update report.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetChange {
+    pub id: String,
+    /// current name of the set
+    pub name: String,
+    /// value of the property before the update
+    pub previous: String,
+    /// value of the property after the update
+    pub current: String
+}
+
+/// Differences between the locally stored sets of a game before and after a set update.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct SetUpdateReport {
+    /// sets that were not listed before
+    pub added: Vec<SetSummary>,
+    /// sets whose name has changed
+    pub renamed: Vec<SetChange>,
+    /// sets whose release date has changed
+    #[serde(rename = "releaseDateChanged")]
+    pub release_date_changed: Vec<SetChange>,
+    /// sets that are no longer listed
+    pub removed: Vec<SetSummary>,
+    /// collection entries that reference a renamed, changed or removed set
+    #[serde(rename = "affectedEntries")]
+    pub affected_entries: Vec<EntrySetReference>
+}
 
 /// Get all sets as a JSON list corresponding to the provided game from the local sets.json file that
-/// belongs to this game. If this file does not exist, the function will return an error.
+/// belongs to this game, merged with the user-defined sets from the local custom_sets.json file.
+/// Fetched sets are filtered according to the app configuration, so that changes of the configuration
+/// take effect without fetching the sets again. Sets that are referenced by entries of the collection
+/// are always included, so these entries can still be edited. If the sets.json file does not exist,
+/// the function will return an error.
 /// 
 /// # Arguments
 /// `game`  - Game name for which the sets should be returned
 /// 
 /// # Returns
-/// string with a list of set objects if the corresponding file exists
+/// string with a list of set objects, sorted by release date, if the corresponding file exists
 /// 
-pub fn get_sets_json<'a>(game: &'a str) -> Result<String, &'a str> {
+pub fn get_sets_json<'a, T: Serialize + DeserializeOwned + SetEntry>(game: &'a str) -> Result<String, &'a str> {
+    let config: Configuration = load_configuration().unwrap();
+    let mut sets: Vec<T> = load_sets::<T>(game)?;
+    let references: Vec<EntrySetReference> = get_set_references(game).unwrap_or_default();
+    sets.retain(|set| set.is_selectable(&config) || references.iter().any(|reference| reference.set_id == set.get_id()));
+    match serde_json::to_string(&sets) {
+        Ok(json) => Ok(json),
+        Err(_) => Err("Unable to serialize sets to JSON.")
+    }
+}
+
+/// Load all sets corresponding to the provided game from the local sets.json file that belongs 
+/// to this game, merged with the user-defined sets from the local custom_sets.json file.
+/// If a custom set has the same id as a fetched set, the custom set takes precedence. If the
+/// sets.json file does not exist, the function will return an error.
+/// 
+/// # Arguments
+/// `game`  - Game name for which the sets should be loaded
+/// 
+/// # Returns
+/// Vector of all sets of the game, sorted by release date
+/// 
+pub fn load_sets<'a, T: DeserializeOwned + SetEntry>(game: &str) -> Result<Vec<T>, &'a str> {
+    let fetched_sets: Vec<T> = load_fetched_sets::<T>(game)?;
+    let custom_sets: Vec<T> = load_custom_sets::<T>(game).unwrap();
+
+    let mut sets: Vec<T> = fetched_sets
+        .into_iter()
+        .filter(|set| !custom_sets.iter().any(|custom| custom.get_id() == set.get_id()))
+        .collect();
+    sets.extend(custom_sets);
+    sets.sort_by(|a, b| a.get_release_date().cmp(b.get_release_date()));
+    Ok(sets)
+}
+
+/// Load the sets corresponding to the provided game from the local sets.json file only, without
+/// any user-defined sets. If this file does not exist, the function will return an error.
+/// 
+/// # Arguments
+/// `game`  - Game name for which the sets should be loaded
+/// 
+pub fn load_fetched_sets<'a, T: DeserializeOwned>(game: &str) -> Result<Vec<T>, &'a str> {
     let config: Configuration = load_configuration().unwrap();
 
     let set_file = format!("{}/{}/sets.json", &config.data_storage, game);
@@ -23,7 +141,7 @@ pub fn get_sets_json<'a>(game: &'a str) -> Result<String, &'a str> {
         let mut data = String::new();
         let mut f = File::open(&set_file_path).expect("Unable to open file stream.");
         f.read_to_string(&mut data).expect("Unable to read file to string.");
-        Ok(data)
+        Ok(serde_json::from_str(&data).expect("Unable to deserialize sets."))
     }
     else {
         Err("File does not exist.")
@@ -36,11 +154,113 @@ pub fn get_sets_json<'a>(game: &'a str) -> Result<String, &'a str> {
 /// `game`  - Game name to specify to which game the provided sets belong
 /// `sets`  - Vector of sets that should be stored in the set file corresponding to the provided game
 /// 
-pub fn store_sets<'a, T: Serialize>(game: &'a str, sets: &Vec<T>) -> Result<(), &'a str> {
+pub fn store_sets<'a, T: Serialize + DeserializeOwned + SetEntry>(game: &'a str, sets: &Vec<T>) -> Result<(), &'a str> {
+    // assets of user-defined sets are kept, since they are not part of the fetched sets
+    let custom_sets: Vec<T> = load_custom_sets::<T>(game).unwrap_or_default();
+    let merged_sets: Vec<&T> = sets.iter()
+        .filter(|set| !custom_sets.iter().any(|custom| custom.get_id() == set.get_id()))
+        .chain(custom_sets.iter())
+        .collect();
+    set_asset_templates::remove_outdated_set_assets::<T>(game, &merged_sets)?;
+    store_set_file::<T>(game, "sets.json", sets)
+}
+
+/// Add the provided object as a new user-defined set to the custom set file of the provided game.
+/// The id of the new set must neither be used by another custom set nor by a fetched set.
+/// 
+/// # Arguments
+/// `game`  - Game name to specify to which game the custom set belongs
+/// `obj`   - New set as JSON string
+/// 
+pub fn add_custom_set<'a, T: Serialize + DeserializeOwned + SetEntry>(game: &'a str, obj: &str) -> Result<(), &'a str> {
+    let mut set: T = serde_json::from_str(obj).expect("Unable to deserialize set object.");
+    set.set_custom(true);
+
+    let sets: Vec<T> = load_sets::<T>(game).unwrap_or_default();
+    if sets.iter().any(|existing| existing.get_id() == set.get_id()) {
+        return Err("A set with this id already exists.");
+    }
+
+    let mut custom_sets: Vec<T> = load_custom_sets::<T>(game).unwrap();
+    custom_sets.push(set);
+    store_set_file::<T>(game, "custom_sets.json", &custom_sets)
+}
+
+/// Update an existing user-defined set of the provided game with the provided object.
+/// The id within the provided object specifies, which custom set should be updated.
+/// 
+/// # Arguments
+/// `game`  - Game name to specify to which game the custom set belongs
+/// `obj`   - Set as JSON string
+/// 
+pub fn update_custom_set<'a, T: Serialize + DeserializeOwned + SetEntry>(game: &'a str, obj: &str) -> Result<(), &'a str> {
+    let mut set: T = serde_json::from_str(obj).expect("Unable to deserialize set object.");
+    set.set_custom(true);
+
+    let mut custom_sets: Vec<T> = load_custom_sets::<T>(game).unwrap();
+    match custom_sets.iter().position(|existing| existing.get_id() == set.get_id()) {
+        Some(index) => custom_sets[index] = set,
+        None => return Err("Unable to find custom set.")
+    }
+    store_set_file::<T>(game, "custom_sets.json", &custom_sets)
+}
+
+/// Delete the user-defined set with the specified `id` from the custom set file of the provided game.
+/// 
+/// # Arguments
+/// `game`  - Game name to specify to which game the custom set belongs
+/// `id`    - Id of the custom set that should be deleted
+/// 
+pub fn delete_custom_set<'a, T: Serialize + DeserializeOwned + SetEntry>(game: &'a str, id: &str) -> Result<(), &'a str> {
+    let mut custom_sets: Vec<T> = load_custom_sets::<T>(game).unwrap();
+    let count = custom_sets.len();
+    custom_sets.retain(|existing| existing.get_id() != id);
+    if custom_sets.len() == count {
+        return Err("Unable to find custom set.");
+    }
+    store_set_file::<T>(game, "custom_sets.json", &custom_sets)
+}
+
+/// Load the user-defined sets of the provided game from the corresponding custom set file.
+/// If this file does not exist, an empty vector will be returned.
+/// 
+/// # Arguments
+/// `game`  - Game name to specify which custom sets should be loaded
+/// 
+fn load_custom_sets<'a, T: DeserializeOwned + SetEntry>(game: &str) -> Result<Vec<T>, &'a str> {
+    let config: Configuration = load_configuration().unwrap();
+
+    let custom_set_file = format!("{}/{}/custom_sets.json", &config.data_storage, game);
+    let custom_set_file_path = Path::new(&custom_set_file);
+
+    if custom_set_file_path.exists() {
+        let mut data = String::new();
+        let mut f = File::open(&custom_set_file_path).expect("Unable to open file stream.");
+        f.read_to_string(&mut data).expect("Unable to read file to string.");
+        let mut custom_sets: Vec<T> = serde_json::from_str(&data).expect("Unable to deserialize custom sets.");
+        for set in custom_sets.iter_mut() {
+            set.set_custom(true);
+        }
+        Ok(custom_sets)
+    }
+    else {
+        Ok(Vec::new())
+    }
+}
+
+/// Store the provided vector of sets to the file with the provided name within the 
+/// directory of the provided game.
+/// 
+/// # Arguments
+/// `game`      - Game name to specify to which game the provided sets belong
+/// `filename`  - Name of the set file, e.g. `sets.json` or `custom_sets.json`
+/// `sets`      - Vector of sets that should be stored
+/// 
+fn store_set_file<'a, T: Serialize>(game: &str, filename: &str, sets: &Vec<T>) -> Result<(), &'a str> {
     let config: Configuration = load_configuration().unwrap();
 
     let game_dir = format!("{}/{}", &config.data_storage, game);
-    let set_file = format!("{}/{}/sets.json", &config.data_storage, game);
+    let set_file = format!("{}/{}/{}", &config.data_storage, game, filename);
     let game_dir_path = Path::new(&game_dir);
     let set_file_path = Path::new(&set_file);
 
@@ -50,7 +270,266 @@ pub fn store_sets<'a, T: Serialize>(game: &'a str, sets: &Vec<T>) -> Result<(),
     }
 
     let set_json = serde_json::to_string(sets).expect("Unable to serialize set data to JSON.");
-    let mut set_file = File::create(&set_file_path).expect("Unable to create 'sets.json' file.");
-    set_file.write_all(&set_json.as_bytes()).expect("Unable to write JSON-serialized sets to 'set.json'.");
+    let mut set_file = File::create(&set_file_path).expect("Unable to create set file.");
+    set_file.write_all(&set_json.as_bytes()).expect("Unable to write JSON-serialized sets to set file.");
+    Ok(())
+}
+
+/// Perform a conditional GET request for the set data of the provided game, based on the metadata
+/// of the last fetch. If the server reports that the data has not been modified since then, only the
+/// fetch timestamp is updated and `None` is returned. Otherwise, the deserialized response is returned
+/// together with the metadata of this fetch, which should be stored via `store_fetch_metadata` once
+/// the sets have been stored.
+/// 
+/// # Arguments
+/// `game`      - Game name to specify to which game the set data belongs
+/// `url`       - URL of the set data
+/// `query`     - Query parameters that should be appended to the URL
+/// `headers`   - Additional request headers, e.g. API keys
+/// 
+pub fn fetch_sets<'a, R: DeserializeOwned>(game: &str, url: &str, query: &[(&str, String)], headers: &[(&str, &str)]) -> Result<Option<(R, SetFetchMetadata)>, &'a str> {
+    let mut metadata: SetFetchMetadata = load_fetch_metadata(game);
+
+    let mut request_headers: Vec<(&str, &str)> = http::get_conditional_headers(&metadata.etag, &metadata.last_modified);
+    request_headers.extend_from_slice(headers);
+    let resp = http::get(url, query, &request_headers)?;
+
+    if resp.status() == StatusCode::NOT_MODIFIED {
+        metadata.fetched_at = get_timestamp();
+        store_fetch_metadata(game, &metadata)?;
+        return Ok(None);
+    }
+
+    let (etag, last_modified) = http::get_cache_validators(&resp);
+    match resp.json::<R>() {
+        Ok(data) => Ok(Some((data, SetFetchMetadata { fetched_at: get_timestamp(), etag: etag, last_modified: last_modified }))),
+        Err(_) => Err("Unable to deserialize set data.")
+    }
+}
+
+/// Load the metadata of the last set fetch of the provided game. If there is no metadata or
+/// no sets.json file, default metadata is returned, which leads to an unconditional fetch.
+/// 
+/// # Arguments
+/// `game`  - Game name for which the metadata should be loaded
+/// 
+pub fn load_fetch_metadata(game: &str) -> SetFetchMetadata {
+    let config: Configuration = load_configuration().unwrap();
+
+    let set_file = format!("{}/{}/sets.json", &config.data_storage, game);
+    let metadata_file = format!("{}/{}/sets_meta.json", &config.data_storage, game);
+    let metadata_file_path = Path::new(&metadata_file);
+
+    if Path::new(&set_file).exists() && metadata_file_path.exists() {
+        let mut data = String::new();
+        let mut f = File::open(&metadata_file_path).expect("Unable to open file stream.");
+        f.read_to_string(&mut data).expect("Unable to read file to string.");
+        serde_json::from_str(&data).unwrap_or_default()
+    }
+    else {
+        SetFetchMetadata::default()
+    }
+}
+
+/// Store the provided metadata of a set fetch next to the sets.json file of the provided game.
+/// 
+/// # Arguments
+/// `game`      - Game name to specify to which game the metadata belongs
+/// `metadata`  - Metadata of the set fetch
+/// 
+pub fn store_fetch_metadata<'a>(game: &str, metadata: &SetFetchMetadata) -> Result<(), &'a str> {
+    let config: Configuration = load_configuration().unwrap();
+
+    let metadata_file = format!("{}/{}/sets_meta.json", &config.data_storage, game);
+    let metadata_json = serde_json::to_string(metadata).expect("Unable to serialize set fetch metadata to JSON.");
+    let mut metadata_file = File::create(&metadata_file).expect("Unable to create 'sets_meta.json' file.");
+    metadata_file.write_all(&metadata_json.as_bytes()).expect("Unable to write set fetch metadata to 'sets_meta.json'.");
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Check whether the locally stored sets of the provided game are older than the maximum age
+/// from the configuration. If the automatic refresh is disabled, this is never the case, unless
+/// the sets were never fetched, e.g. since they were taken from the bundled set snapshot.
+/// 
+/// # Arguments
+/// `game`  - Game name for which the sets should be checked
+/// 
+pub fn sets_outdated(game: &str) -> bool {
+    let config: Configuration = load_configuration().unwrap();
+    let metadata: SetFetchMetadata = load_fetch_metadata(game);
+    is_outdated(metadata.fetched_at, config.set_max_age_hours, get_timestamp())
+}
+
+/// Check whether sets that were fetched at the provided point in time are older than the provided maximum age
+/// at the provided current point in time, see `sets_outdated`. Points in time are seconds since the unix epoch.
+fn is_outdated(fetched_at: u64, max_age_hours: u64, now: u64) -> bool {
+    if fetched_at == 0 {
+        return true;
+    }
+    if max_age_hours == 0 {
+        return false;
+    }
+    now.saturating_sub(fetched_at) > max_age_hours * 3600
+}
+
+/// Run the provided set update function in a background thread, unless an update that is
+/// guarded by the provided flag is already running.
+/// 
+/// # Arguments
+/// `running`       - Flag that indicates whether a background update of the game is running
+/// `update_sets`   - Set update function of the game
+/// 
+pub fn refresh_sets_in_background<T: 'static>(running: &'static AtomicBool, update_sets: fn() -> Result<Vec<T>, &'static str>) {
+    if running.compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst).is_err() {
+        return;
+    }
+    thread::spawn(move || {
+        let _ = update_sets();
+        running.store(false, Ordering::SeqCst);
+    });
+}
+
+fn get_timestamp() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
+
+/// Compare the provided sets of the game before and after a set update and return the differences,
+/// including the collection entries that reference a renamed, changed or removed set, as JSON string.
+/// 
+/// # Arguments
+/// `game`      - Game name to specify to which game the sets belong
+/// `previous`  - Sets before the update
+/// `current`   - Sets after the update
+/// 
+/// # Returns
+/// string with a JSON encoded `SetUpdateReport`
+/// 
+pub fn get_set_update_report_json<'a, T: SetEntry>(game: &str, previous: &Vec<T>, current: &Vec<T>) -> Result<String, &'a str> {
+    let mut report: SetUpdateReport = get_set_update_report(previous, current);
+
+    // collection entries that are affected by any change
+    let changed_set_ids: Vec<&str> = report.renamed.iter().map(|change| change.id.as_str())
+        .chain(report.release_date_changed.iter().map(|change| change.id.as_str()))
+        .chain(report.removed.iter().map(|set| set.id.as_str()))
+        .collect();
+    if !changed_set_ids.is_empty() {
+        report.affected_entries = get_set_references(game)?
+            .into_iter()
+            .filter(|reference| changed_set_ids.contains(&reference.set_id.as_str()))
+            .collect();
+    }
+
+    match serde_json::to_string(&report) {
+        Ok(json) => Ok(json),
+        Err(_) => Err("Unable to serialize set update report to JSON.")
+    }
+}
+
+/// Compare the provided sets before and after a set update and return the added, renamed, changed
+/// and removed sets. The affected collection entries are left empty.
+fn get_set_update_report<T: SetEntry>(previous: &Vec<T>, current: &Vec<T>) -> SetUpdateReport {
+    let summarize = |set: &T| SetSummary { 
+        id: set.get_id().to_string(), 
+        name: set.get_name().to_string(), 
+        release_date: set.get_release_date().to_string() 
+    };
+
+    let mut report = SetUpdateReport::default();
+    for set in current.iter() {
+        match previous.iter().find(|previous_set| previous_set.get_id() == set.get_id()) {
+            None => report.added.push(summarize(set)),
+            Some(previous_set) => {
+                if previous_set.get_name() != set.get_name() {
+                    report.renamed.push(SetChange {
+                        id: set.get_id().to_string(),
+                        name: set.get_name().to_string(),
+                        previous: previous_set.get_name().to_string(),
+                        current: set.get_name().to_string()
+                    });
+                }
+                if previous_set.get_release_date() != set.get_release_date() {
+                    report.release_date_changed.push(SetChange {
+                        id: set.get_id().to_string(),
+                        name: set.get_name().to_string(),
+                        previous: previous_set.get_release_date().to_string(),
+                        current: set.get_release_date().to_string()
+                    });
+                }
+            }
+        }
+    }
+    for previous_set in previous.iter() {
+        if !current.iter().any(|set| set.get_id() == previous_set.get_id()) {
+            report.removed.push(summarize(previous_set));
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::magic::set_services::Set;
+
+    fn get_set(id: &str, name: &str, release_date: &str) -> Set {
+        serde_json::from_value(json!({"id": id, "name": name, "releaseDate": release_date})).unwrap()
+    }
+
+    #[test]
+    fn is_outdated_respects_max_age() {
+        let fetched_at: u64 = 1_700_000_000;
+        assert!(!is_outdated(fetched_at, 24, fetched_at + 24 * 3600));
+        assert!(is_outdated(fetched_at, 24, fetched_at + 24 * 3600 + 1));
+        // clocks that were set back do not outdate the sets
+        assert!(!is_outdated(fetched_at, 24, fetched_at - 3600));
+    }
+
+    #[test]
+    fn is_outdated_never_refreshes_fetched_sets_without_max_age() {
+        assert!(!is_outdated(1_700_000_000, 0, 1_800_000_000));
+    }
+
+    #[test]
+    fn is_outdated_refreshes_sets_that_were_never_fetched() {
+        assert!(is_outdated(0, 24, 1_700_000_000));
+        assert!(is_outdated(0, 0, 1_700_000_000));
+    }
+
+    #[test]
+    fn set_update_report_lists_added_changed_and_removed_sets() {
+        let previous: Vec<Set> = vec![
+            get_set("lea", "Alpha", "1993/08/05"),
+            get_set("leb", "Beta", "1993/10/01"),
+            get_set("arn", "Arabian Nights", "1993/12/17")
+        ];
+        let current: Vec<Set> = vec![
+            get_set("lea", "Limited Edition Alpha", "1993/08/05"),
+            get_set("leb", "Beta", "1993/10/04"),
+            get_set("dmu", "Dominaria United", "2022/09/09")
+        ];
+        let report: SetUpdateReport = get_set_update_report(&previous, &current);
+
+        assert_eq!(report.added.iter().map(|set| set.id.as_str()).collect::<Vec<&str>>(), vec!["dmu"]);
+        assert_eq!(report.removed.iter().map(|set| set.id.as_str()).collect::<Vec<&str>>(), vec!["arn"]);
+        assert_eq!(report.renamed.len(), 1);
+        assert_eq!(report.renamed[0].id, "lea");
+        assert_eq!(report.renamed[0].previous, "Alpha");
+        assert_eq!(report.renamed[0].current, "Limited Edition Alpha");
+        assert_eq!(report.release_date_changed.len(), 1);
+        assert_eq!(report.release_date_changed[0].id, "leb");
+        assert_eq!(report.release_date_changed[0].previous, "1993/10/01");
+        assert_eq!(report.release_date_changed[0].current, "1993/10/04");
+        assert!(report.affected_entries.is_empty());
+    }
+
+    #[test]
+    fn set_update_report_is_empty_for_unchanged_sets() {
+        let sets: Vec<Set> = vec![get_set("lea", "Alpha", "1993/08/05"), get_set("leb", "Beta", "1993/10/01")];
+        let report: SetUpdateReport = get_set_update_report(&sets, &sets);
+        assert!(report.added.is_empty());
+        assert!(report.renamed.is_empty());
+        assert!(report.release_date_changed.is_empty());
+        assert!(report.removed.is_empty());
+    }
+}
@@ -0,0 +1,298 @@
+use std::io::{Read, Write};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::fs::{File, create_dir_all, read_dir, remove_file};
+use std::process;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::thread;
+use std::time::UNIX_EPOCH;
+use serde::{Serialize, Deserialize};
+use sha2::{Sha256, Digest};
+use image::{DynamicImage, ImageOutputFormat};
+
+use crate::util::config::{Configuration, load_configuration};
+use crate::templates::card_service_templates::get_entry_image_path;
+use crate::templates::image_templates::open_oriented_image;
+
+/// Lock for the thumbnail index of all games, since thumbnails are generated in the
+/// background while the frontend requests thumbnails as well.
+static INDEX_LOCK: Mutex<()> = Mutex::new(());
+
+/// Thumbnails that are generated in the background at the moment, so each is generated only once.
+static PENDING_THUMBNAILS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// Counter of the temporary files of thumbnails, so concurrent writers never share a temporary file.
+static TMP_COUNTER: AtomicU32 = AtomicU32::new(0);
+
+/// Source image of thumbnails, as stored within the thumbnail index at
+/// `<storage_location>/<game>/thumbnails/index.json`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct ThumbnailSource {
+    /// SHA-256 hash of the content of the source image, used as key of its thumbnails
+    hash: String,
+    /// size of the source image in bytes, when the hash was calculated
+    len: u64,
+    /// modification time of the source image in nanoseconds since the unix epoch, when the hash was calculated
+    modified: u64
+}
+
+/// Get the location of the thumbnail of the provided image with the provided size. If the requested
+/// size is not configured, the next larger configured size is used. The thumbnail is generated, if it
+/// does not exist yet or if the image changed since its thumbnails were generated.
+///
+/// # Arguments
+/// `game`  -   Game name to specify to which game the image belongs
+/// `image` -   Name of the image
+/// `size`  -   Requested maximum width and height of the thumbnail in pixels
+///
+/// # Returns
+/// Location of the thumbnail file
+///
+pub fn get_thumbnail_path<'a>(game: &str, image: &str, size: u32) -> Result<PathBuf, &'a str> {
+    let (source, thumbnail, size) = resolve_thumbnail(game, image, size)?;
+    if !thumbnail.exists() {
+        create_thumbnail(&source, &thumbnail, size)?;
+    }
+    Ok(thumbnail)
+}
+
+/// Get the location of the thumbnail of the provided image with the provided size, if the thumbnail
+/// exists already. Otherwise, the thumbnail is generated in the background, so the caller is not blocked
+/// and can use the image itself in the meantime, see `get_thumbnail_path`.
+///
+/// # Arguments
+/// `game`  -   Game name to specify to which game the image belongs
+/// `image` -   Name of the image
+/// `size`  -   Requested maximum width and height of the thumbnail in pixels
+///
+/// # Returns
+/// Location of the thumbnail file, if it exists
+///
+pub fn find_thumbnail_path<'a>(game: &str, image: &str, size: u32) -> Result<Option<PathBuf>, &'a str> {
+    let (source, thumbnail, size) = resolve_thumbnail(game, image, size)?;
+    if thumbnail.exists() {
+        return Ok(Some(thumbnail));
+    }
+    let mut pending = PENDING_THUMBNAILS.lock().unwrap();
+    if !pending.contains(&thumbnail) {
+        pending.push(thumbnail.clone());
+        thread::spawn(move || {
+            let _ = create_thumbnail(&source, &thumbnail, size);
+            PENDING_THUMBNAILS.lock().unwrap().retain(|pending_thumbnail| *pending_thumbnail != thumbnail);
+        });
+    }
+    Ok(None)
+}
+
+/// Generate the thumbnails of all configured sizes for the provided image in the background.
+///
+/// # Arguments
+/// `game`  -   Game name to specify to which game the image belongs
+/// `image` -   Name of the image
+///
+pub fn generate_thumbnails_in_background(game: &str, image: &str) {
+    let game = game.to_string();
+    let image = image.to_string();
+    thread::spawn(move || {
+        let config: Configuration = load_configuration().unwrap();
+        for size in config.images.thumbnail_sizes.iter() {
+            if get_thumbnail_path(&game, &image, *size).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// Get the location of the provided image, the location of its thumbnail with the provided size and
+/// the configured thumbnail size that is used for the requested size.
+fn resolve_thumbnail<'a>(game: &str, image: &str, size: u32) -> Result<(PathBuf, PathBuf, u32), &'a str> {
+    let config: Configuration = load_configuration().unwrap();
+    let size: u32 = select_thumbnail_size(&config.images.thumbnail_sizes, size);
+    let source: PathBuf = get_entry_image_path(game, image)?;
+    let hash: String = get_source_hash(game, image, &source)?;
+    let thumbnail: PathBuf = get_thumbnail_dir(game).join(get_thumbnail_filename(&hash, size, &source));
+    Ok((source, thumbnail, size))
+}
+
+/// Remove the provided image from the thumbnail index and delete its thumbnails, unless they
+/// are shared with another image of the same content.
+///
+/// # Arguments
+/// `game`  -   Game name to specify to which game the image belongs
+/// `image` -   Name of the image
+///
+pub fn remove_thumbnails<'a>(game: &str, image: &str) -> Result<(), &'a str> {
+    let _lock = INDEX_LOCK.lock().unwrap();
+    let mut index: HashMap<String, ThumbnailSource> = load_thumbnail_index(game);
+    if let Some(source) = index.remove(image) {
+        store_thumbnail_index(game, &index)?;
+        remove_unreferenced_thumbnails(game, &index, &source.hash);
+    }
+    Ok(())
+}
+
+/// Move the provided image to its new name within the thumbnail index. Since thumbnails are stored by the
+/// content hash of their image, the thumbnails themselves are kept.
+///
+/// # Arguments
+/// `game`      -   Game name to specify to which game the image belongs
+/// `image`     -   Previous name of the image
+/// `new_image` -   New name of the image
+///
+pub fn rename_thumbnails<'a>(game: &str, image: &str, new_image: &str) -> Result<(), &'a str> {
+    let _lock = INDEX_LOCK.lock().unwrap();
+    let mut index: HashMap<String, ThumbnailSource> = load_thumbnail_index(game);
+    if let Some(source) = index.remove(image) {
+        let previous: Option<ThumbnailSource> = index.insert(new_image.to_string(), source);
+        store_thumbnail_index(game, &index)?;
+        if let Some(previous) = previous {
+            remove_unreferenced_thumbnails(game, &index, &previous.hash);
+        }
+    }
+    Ok(())
+}
+
+/// Get the content hash of the provided image. The hash is taken from the thumbnail index, unless
+/// the image changed since it was calculated. In this case, the hash is recalculated and the thumbnails
+/// of the previous content are deleted.
+fn get_source_hash<'a>(game: &str, image: &str, source: &Path) -> Result<String, &'a str> {
+    let metadata = match source.metadata() {
+        Ok(metadata) if metadata.is_file() => metadata,
+        _ => return Err("Unable to find image.")
+    };
+    let len: u64 = metadata.len();
+    let modified: u64 = metadata.modified().ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |modified| modified.as_nanos() as u64);
+
+    let _lock = INDEX_LOCK.lock().unwrap();
+    let mut index: HashMap<String, ThumbnailSource> = load_thumbnail_index(game);
+    if let Some(entry) = index.get(image).filter(|entry| entry.len == len && entry.modified == modified) {
+        return Ok(entry.hash.clone());
+    }
+
+    let entry = ThumbnailSource { hash: get_content_hash(source)?, len: len, modified: modified };
+    let hash: String = entry.hash.clone();
+    let previous: Option<ThumbnailSource> = index.insert(image.to_string(), entry);
+    store_thumbnail_index(game, &index)?;
+    if let Some(previous) = previous.filter(|previous| previous.hash != hash) {
+        remove_unreferenced_thumbnails(game, &index, &previous.hash);
+    }
+    Ok(hash)
+}
+
+/// Get the SHA-256 hash of the content of the provided file as hex string.
+pub fn get_content_hash<'a>(path: &Path) -> Result<String, &'a str> {
+    let mut f = match File::open(path) {
+        Ok(f) => f,
+        Err(_) => return Err("Unable to open image.")
+    };
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        match f.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => hasher.update(&buffer[..read]),
+            Err(_) => return Err("Unable to read image.")
+        }
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Scale the provided source image down to the provided size and store it as thumbnail. JPEG
+/// images are stored as JPEG, all other images as PNG to keep their transparency. Images that
+/// are already small enough are not scaled up. Images are rotated according to their EXIF orientation.
+fn create_thumbnail<'a>(source: &Path, thumbnail: &Path, size: u32) -> Result<(), &'a str> {
+    let img: DynamicImage = open_oriented_image(source)?;
+    let img: DynamicImage = if img.width() > size || img.height() > size {
+        img.thumbnail(size, size)
+    }
+    else {
+        img
+    };
+
+    let format = match is_jpeg(source) {
+        true => ImageOutputFormat::Jpeg(85),
+        false => ImageOutputFormat::Png
+    };
+    let mut data: Vec<u8> = Vec::new();
+    if img.write_to(&mut std::io::Cursor::new(&mut data), format).is_err() {
+        return Err("Unable to encode thumbnail.");
+    }
+
+    if let Some(thumbnail_dir) = thumbnail.parent() {
+        create_dir_all(thumbnail_dir).unwrap();
+    }
+    // write to a temporary file first, so a thumbnail that is requested concurrently is never incomplete.
+    // Each writer uses its own temporary file, since the same thumbnail might be generated concurrently.
+    let tmp: PathBuf = thumbnail.with_extension(format!("{}-{}.tmp", process::id(), TMP_COUNTER.fetch_add(1, Ordering::SeqCst)));
+    let mut f = match File::create(&tmp) {
+        Ok(f) => f,
+        Err(_) => return Err("Unable to create thumbnail file.")
+    };
+    if f.write_all(&data).is_err() || std::fs::rename(&tmp, thumbnail).is_err() {
+        let _ = remove_file(&tmp);
+        return Err("Unable to write thumbnail file.");
+    }
+    Ok(())
+}
+
+/// Delete all thumbnails of the provided content hash, if no image within the provided index has this hash.
+fn remove_unreferenced_thumbnails(game: &str, index: &HashMap<String, ThumbnailSource>, hash: &str) {
+    if index.values().any(|source| source.hash == hash) {
+        return;
+    }
+    if let Ok(entries) = read_dir(get_thumbnail_dir(game)) {
+        let prefix = format!("{}-", hash);
+        for entry in entries.flatten() {
+            if entry.file_name().to_str().map_or(false, |filename| filename.starts_with(&prefix)) {
+                let _ = remove_file(entry.path());
+            }
+        }
+    }
+}
+
+/// Select the smallest configured size that is at least the requested size or the largest configured
+/// size, if all are smaller. If no sizes are configured, the requested size is used.
+fn select_thumbnail_size(sizes: &Vec<u32>, requested: u32) -> u32 {
+    sizes.iter().filter(|size| **size >= requested).min()
+        .or_else(|| sizes.iter().max())
+        .cloned()
+        .unwrap_or(requested)
+        .max(1)
+}
+
+fn get_thumbnail_filename(hash: &str, size: u32, source: &Path) -> String {
+    format!("{}-{}.{}", hash, size, if is_jpeg(source) { "jpg" } else { "png" })
+}
+
+fn is_jpeg(path: &Path) -> bool {
+    let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+    matches!(extension.to_lowercase().as_str(), "jpg" | "jpeg")
+}
+
+fn get_thumbnail_dir(game: &str) -> PathBuf {
+    let config: Configuration = load_configuration().unwrap();
+    Path::new(&config.data_storage).join(game).join("thumbnails")
+}
+
+fn load_thumbnail_index(game: &str) -> HashMap<String, ThumbnailSource> {
+    let mut data = String::new();
+    match File::open(get_thumbnail_dir(game).join("index.json")) {
+        Ok(mut f) => {
+            f.read_to_string(&mut data).expect("Unable to read thumbnail index.");
+            serde_json::from_str(&data).unwrap_or_default()
+        },
+        Err(_) => HashMap::new()
+    }
+}
+
+fn store_thumbnail_index<'a>(game: &str, index: &HashMap<String, ThumbnailSource>) -> Result<(), &'a str> {
+    let thumbnail_dir: PathBuf = get_thumbnail_dir(game);
+    create_dir_all(&thumbnail_dir).unwrap();
+    let json = serde_json::to_string(index).expect("Unable to serialize thumbnail index to JSON.");
+    let mut f = File::create(thumbnail_dir.join("index.json")).expect("Unable to create thumbnail index.");
+    f.write_all(json.as_bytes()).expect("Unable to write thumbnail index.");
+    Ok(())
+}
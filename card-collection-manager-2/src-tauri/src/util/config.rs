@@ -14,7 +14,159 @@ pub struct Configuration {
     pub data_storage: String,
     /// Default game to start the app with
     #[serde(rename = "defaultGame")]
-    pub default_game: Game
+    pub default_game: Game,
+    /// Maximum age of the locally stored sets in hours, before they are refreshed automatically
+    /// in the background. A value of `0` disables the automatic refresh.
+    #[serde(rename = "setMaxAgeHours", default = "default_set_max_age_hours")]
+    pub set_max_age_hours: u64,
+    /// Magic specific configuration
+    #[serde(default)]
+    pub magic: MagicConfiguration,
+    /// Pokemon specific configuration
+    #[serde(default)]
+    pub pokemon: PokemonConfiguration,
+    /// HTTP client configuration
+    #[serde(default)]
+    pub http: HttpConfiguration,
+    /// Configuration of the collection images
+    #[serde(default)]
+    pub images: ImageConfiguration
+}
+
+fn default_set_max_age_hours() -> u64 {
+    168
+}
+
+/// Magic specific configuration
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct MagicConfiguration {
+    /// Base URL of the Scryfall API, can be changed to point to a local mirror
+    #[serde(rename = "apiUrl")]
+    pub api_url: String,
+    /// Scryfall set types (e.g. `core`, `expansion`, `token`) of sets that should be available 
+    /// for selection. If empty, sets of all types are available.
+    #[serde(rename = "setTypes")]
+    pub set_types: Vec<String>,
+    /// Flag to indicate whether sets that were only released digitally should be available for selection
+    #[serde(rename = "digitalSets")]
+    pub digital_sets: bool
+}
+
+impl Default for MagicConfiguration {
+    fn default() -> Self {
+        MagicConfiguration { 
+            api_url: String::from("https://api.scryfall.com"),
+            set_types: Vec::new(), 
+            digital_sets: false 
+        }
+    }
+}
+
+/// Pokemon specific configuration
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct PokemonConfiguration {
+    /// Base URL of the Pokemon TCG API, can be changed to point to a local mirror
+    #[serde(rename = "apiUrl")]
+    pub api_url: String,
+    /// Optional API key for `https://api.pokemontcg.io`, which grants higher rate limits
+    #[serde(rename = "apiKey")]
+    pub api_key: Option<String>
+}
+
+impl Default for PokemonConfiguration {
+    fn default() -> Self {
+        PokemonConfiguration { 
+            api_url: String::from("https://api.pokemontcg.io/v2"), 
+            api_key: None 
+        }
+    }
+}
+
+/// Configuration of the HTTP client that is used for all requests to external APIs
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct HttpConfiguration {
+    /// Timeout of a single request in seconds
+    #[serde(rename = "timeoutSecs")]
+    pub timeout_secs: u64,
+    /// Number of times a failed request is retried
+    pub retries: u32,
+    /// Delay before the first retry in milliseconds, which is doubled with each further retry
+    #[serde(rename = "retryBackoffMs")]
+    pub retry_backoff_ms: u64,
+    /// Minimum delay between two consecutive requests in milliseconds
+    #[serde(rename = "requestIntervalMs")]
+    pub request_interval_ms: u64,
+    /// Optional URL of an HTTP proxy that should be used for all requests
+    pub proxy: Option<String>,
+    /// Optional User-Agent that should be sent instead of the default one
+    #[serde(rename = "userAgent")]
+    pub user_agent: Option<String>
+}
+
+impl Default for HttpConfiguration {
+    fn default() -> Self {
+        HttpConfiguration { 
+            timeout_secs: 30, 
+            retries: 3, 
+            retry_backoff_ms: 500, 
+            request_interval_ms: 100, 
+            proxy: None, 
+            user_agent: None 
+        }
+    }
+}
+
+/// Configuration of the collection images
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct ImageConfiguration {
+    /// Sizes in pixels of the generated thumbnails, i.e. the maximum of their width and height
+    #[serde(rename = "thumbnailSizes")]
+    pub thumbnail_sizes: Vec<u32>,
+    /// Template of the filenames of collection images. The placeholders `{id}`, `{setCode}`, `{setName}`, 
+    /// `{number}`, `{name}`, `{language}` and `{index}` are replaced with the values of the image's entry.
+    #[serde(rename = "filenameTemplate")]
+    pub filename_template: String,
+    /// Flag to indicate whether imported images should be normalized, i.e. rotated according to their
+    /// EXIF orientation, stripped of their metadata, scaled down and transcoded. If disabled, imported
+    /// images are copied as they are.
+    pub normalize: bool,
+    /// Maximum width and height in pixels of normalized images. A value of `0` disables the downscaling.
+    #[serde(rename = "maxSize")]
+    pub max_size: u32,
+    /// Format of normalized images
+    pub format: ImageFormat,
+    /// Quality of normalized JPEG and WebP images from `1` to `100`
+    pub quality: u8,
+    /// Flag to indicate whether the original files of normalized images should be kept
+    #[serde(rename = "keepOriginals")]
+    pub keep_originals: bool
+}
+
+impl Default for ImageConfiguration {
+    fn default() -> Self {
+        ImageConfiguration { 
+            thumbnail_sizes: vec![160, 480],
+            filename_template: String::from("{id}+{setName}+{name}+{index}"),
+            normalize: false,
+            max_size: 2048,
+            format: ImageFormat::Jpeg,
+            quality: 85,
+            keep_originals: false
+        }
+    }
+}
+
+/// Format of normalized images
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    Jpeg,
+    Png,
+    Webp
 }
 
 /// Get the application_s configuration from the config file at `config.json` 
@@ -30,7 +182,15 @@ pub fn load_configuration<'a>() -> Result<Configuration, &'a str> {
         Ok(serde_json::from_str(&data).expect("Unable to deserialize configuration."))
     }
     else {
-        let config = Configuration { data_storage: current_dir().unwrap().to_str().unwrap().to_string(), default_game: Game::Magic };
+        let config = Configuration { 
+            data_storage: current_dir().unwrap().to_str().unwrap().to_string(), 
+            default_game: Game::Magic, 
+            set_max_age_hours: default_set_max_age_hours(),
+            magic: MagicConfiguration::default(), 
+            pokemon: PokemonConfiguration::default(), 
+            http: HttpConfiguration::default(),
+            images: ImageConfiguration::default() 
+        };
         store_configuration(&serde_json::to_string(&config).expect("Unable to serialize configuration.")).unwrap();
         load_configuration()
     }
@@ -32,6 +32,21 @@ pub enum Game {
     Pokemon
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, EnumIter)]
+pub enum SetAsset {
+    Symbol,
+    Logo
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, EnumIter)]
+pub enum ImageRole {
+    Front,
+    Back,
+    Detail,
+    GradingLabel,
+    Other
+}
+
 fn get_enum_variants<T: IntoEnumIterator>() -> Vec<T> {
     let mut variants: Vec<T> = Vec::new();
     for variant in T::iter() {
@@ -56,4 +71,10 @@ pub fn get_condition_variants_json() -> Result<String, String> {
 #[tauri::command]
 pub fn get_game_variants_json() -> Result<String, String> {
     Ok(serde_json::to_string(&get_enum_variants::<Game>()).unwrap())
+}
+
+/// Get all possible roles of collection images that are supported by the app.
+#[tauri::command]
+pub fn get_image_role_variants_json() -> Result<String, String> {
+    Ok(serde_json::to_string(&get_enum_variants::<ImageRole>()).unwrap())
 }
\ No newline at end of file
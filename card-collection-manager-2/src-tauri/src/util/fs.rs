@@ -1,49 +1,261 @@
-use nom::character::is_digit;
 use std::path::Path;
 use std::ffi::OsStr;
+use sha2::{Sha256, Digest};
+
+/// Maximum length of a formatted filename in bytes. File systems allow up to 255 bytes, but shorter
+/// names keep the paths of images within the path length limit of Windows.
+const MAX_FILENAME_LEN: usize = 120;
+
+/// File extensions of images, see `split_extension`
+const IMAGE_EXTENSIONS: [&str; 12] = ["jpg", "jpeg", "jfif", "png", "webp", "gif", "bmp", "tif", "tiff", "heic", "heif", "avif"];
+/// Names that Windows reserves for devices, regardless of their case and extension
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL",
+    "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+    "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9"
+];
 
 /// Replace problematic characters in a text in order to 
 /// avoid any conflicts when using the text to create a
-/// file.
-pub fn format_text_for_fs(text: &String) -> String {
-    text
-    .replace("'", "")
-    .replace("`", "")
-    .replace(",","")
-    .replace(" ", "")
-    .replace(":", "-")
-    .replace("&", "And")
-    .replace("|", "Or")
-    .replace("á", "a")
-    .replace("é", "e")
-    .replace("í", "i")
-    .replace("ó", "o")
-    .replace("ú", "u")
-    .replace("û", "u")
+/// file. Latin letters with accents and ligatures are transliterated, e.g. `Æther` becomes `Aether`,
+/// characters that are invalid on any platform are removed and names that Windows reserves, like
+/// `CON`, get an underscore appended. Texts that exceed `MAX_FILENAME_LEN` are shortened and get
+/// a hash of the full text appended, so different texts still result in different filenames.
+pub fn format_text_for_fs(text: &str) -> String {
+    let mut formatted = String::new();
+    for c in text.chars() {
+        match c {
+            '\'' | '\u{2018}' | '\u{2019}' | '`' | ',' | '"' | '<' | '>' | '?' | '*' => (),
+            ':' => formatted.push('-'),
+            '/' | '\\' => formatted.push('-'),
+            '&' => formatted.push_str("And"),
+            '|' => formatted.push_str("Or"),
+            '♀' => formatted.push('F'),
+            '♂' => formatted.push('M'),
+            '★' | '☆' => formatted.push_str("Star"),
+            'Æ' => formatted.push_str("Ae"),
+            'æ' => formatted.push_str("ae"),
+            'Œ' => formatted.push_str("Oe"),
+            'œ' => formatted.push_str("oe"),
+            'ß' => formatted.push_str("ss"),
+            'Þ' => formatted.push_str("Th"),
+            'þ' => formatted.push_str("th"),
+            'Ð' => formatted.push('D'),
+            'ð' => formatted.push('d'),
+            // combining diacritical marks of decomposed characters
+            '\u{0300}'..='\u{036f}' => (),
+            c if c.is_whitespace() || c.is_control() => (),
+            c if c.is_uppercase() => {
+                let lowercase: char = c.to_lowercase().next().unwrap_or(c);
+                match fold_accent(lowercase) {
+                    folded if folded != lowercase => formatted.extend(folded.to_uppercase()),
+                    _ => formatted.push(c)
+                }
+            },
+            c if c.is_alphanumeric() => formatted.push(fold_accent(c)),
+            c if c.is_ascii() => formatted.push(c),
+            // any other symbol, e.g. `™`
+            _ => ()
+        }
+    }
+
+    let (stem, extension) = split_extension(&formatted);
+    // leading dots hide files and trailing dots are removed by Windows
+    let mut stem: String = stem.trim_start_matches('.').trim_end_matches('.').to_string();
+    if stem.is_empty() {
+        stem.push('_');
+    }
+    let device = stem.split('.').next().unwrap_or_default().to_uppercase();
+    if RESERVED_NAMES.contains(&device.as_str()) {
+        stem.insert(device.len(), '_');
+    }
+
+    let suffix: String = extension.map_or(String::new(), |extension| format!(".{}", extension));
+    if stem.len() + suffix.len() > MAX_FILENAME_LEN {
+        let hash = format!("{:x}", Sha256::digest(formatted.as_bytes()));
+        let mut len: usize = MAX_FILENAME_LEN.saturating_sub(suffix.len() + 9);
+        while !stem.is_char_boundary(len) {
+            len -= 1;
+        }
+        stem = format!("{}~{}", &stem[..len], &hash[..8]);
+    }
+    format!("{}{}", stem, suffix)
+}
+
+/// Split the provided filename into its stem and its extension. Only known image extensions count as
+/// extension, so names like `Mr.Mime` have no extension.
+fn split_extension(filename: &str) -> (&str, Option<&str>) {
+    match filename.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() 
+            && IMAGE_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str()) => (stem, Some(extension)),
+        _ => (filename, None)
+    }
 }
 
-/// Parse an index from the end of a filename. This function supports indices with 
-/// 1 and 2 digit indices.
+/// Get a filename based on the provided one, that is not taken yet. If the provided filename is
+/// taken, a number is appended to its stem, e.g. `image-1.png`.
+/// 
+/// # Arguments
+/// `filename`  -   Preferred filename
+/// `is_taken`  -   Function that checks whether a filename is already taken
+/// 
+pub fn get_unique_filename<F: Fn(&str) -> bool>(filename: &str, is_taken: F) -> String {
+    if !is_taken(filename) {
+        return filename.to_string();
+    }
+    let (stem, extension) = split_extension(filename);
+    let suffix: String = extension.map_or(String::new(), |extension| format!(".{}", extension));
+    let mut number: u32 = 1;
+    loop {
+        let candidate = format!("{}-{}{}", stem, number, suffix);
+        if !is_taken(&candidate) {
+            return candidate;
+        }
+        number += 1;
+    }
+}
+
+/// Map the provided lowercase latin letter with accent to the corresponding letter without accent.
+pub fn fold_accent(c: char) -> char {
+    match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => 'a',
+        'ç' | 'ć' | 'ĉ' | 'č' => 'c',
+        'ď' | 'đ' => 'd',
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ė' | 'ę' | 'ě' => 'e',
+        'ĝ' | 'ğ' | 'ģ' => 'g',
+        'ì' | 'í' | 'î' | 'ï' | 'ĩ' | 'ī' | 'į' | 'ı' => 'i',
+        'ķ' => 'k',
+        'ĺ' | 'ļ' | 'ľ' | 'ł' => 'l',
+        'ñ' | 'ń' | 'ņ' | 'ň' => 'n',
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ő' => 'o',
+        'ŕ' | 'ř' => 'r',
+        'ś' | 'ŝ' | 'ş' | 'š' => 's',
+        'ţ' | 'ť' => 't',
+        'ù' | 'ú' | 'û' | 'ü' | 'ũ' | 'ū' | 'ů' | 'ű' | 'ų' => 'u',
+        'ý' | 'ÿ' => 'y',
+        'ź' | 'ż' | 'ž' => 'z',
+        _ => c
+    }
+}
+
+
+/// Parse an index from the end of a filename, i.e. the digits directly before the file extension.
+/// Indices of any length are supported. Filenames without trailing digits, like the names
+/// `IMG_FRONT` and `IMG_BACK` of card collection manager v1, have no index.
 /// 
 /// Example:
-/// image1.png -> 1
-/// image22.jpeg -> 22
+/// image1.png -> Some(1)
+/// image123.jpeg -> Some(123)
+/// image_FRONT.png -> None
 /// 
-pub fn parse_index_from_filename(filename: &str) -> u8 {
-    
-    // get offset according to extension 
-    let file_extension = Path::new(filename).extension().and_then(OsStr::to_str).unwrap();
-    let offset = file_extension.len() + 1;
-
-    let mut index_len = 1;
-    
-    // try if index is 2 digits long
-    let two_digits_test: String = filename.chars().skip(filename.len() - offset - 2).take(1).collect();
-    if is_digit(two_digits_test.as_bytes()[0]) {
-        index_len = 2;
-    }
-    
-    // extract index
-    let index: String = filename.chars().skip(filename.len() - offset - index_len).take(index_len).collect();
-    index.parse::<u8>().unwrap()
+pub fn parse_index_from_filename(filename: &str) -> Option<u32> {
+    let stem = Path::new(filename).file_stem().and_then(OsStr::to_str).unwrap_or(filename);
+    let digits: String = stem.chars().rev().take_while(|c| c.is_ascii_digit()).collect::<Vec<char>>().into_iter().rev().collect();
+    digits.parse::<u32>().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_text_for_fs_handles_card_names() {
+        let names = [
+            ("Æther Vial", "AetherVial"),
+            ("Flabébé", "Flabebe"),
+            ("Nidoran♀", "NidoranF"),
+            ("Nidoran♂", "NidoranM"),
+            ("Jötun Grunt", "JotunGrunt"),
+            ("Lim-Dûl's Vault", "Lim-DulsVault"),
+            ("Dandân", "Dandan"),
+            ("Séance", "Seance"),
+            ("Ifh-Bíff Efreet", "Ifh-BiffEfreet"),
+            ("Ghazbán Ogre", "GhazbanOgre"),
+            ("Márton Stromgald", "MartonStromgald"),
+            ("Œil de Lynx", "OeildeLynx"),
+            ("Farfetch'd", "Farfetchd"),
+            ("Farfetch’d", "Farfetchd"),
+            ("Porygon-Z", "Porygon-Z"),
+            ("Pikachu ★", "PikachuStar"),
+            ("Fire // Ice", "Fire--Ice"),
+            ("Circle of Protection: Red", "CircleofProtection-Red"),
+            ("Borrowing 100,000 Arrows", "Borrowing100000Arrows"),
+            ("R&D's Secret Lair", "RAndDsSecretLair"),
+            ("Kongming, \"Sleeping Dragon\"", "KongmingSleepingDragon"),
+            ("\"Ach! Hans, Run!\"", "Ach!HansRun!"),
+            ("Question Elemental?", "QuestionElemental"),
+            ("Our Market Research Shows That Players Like Really Long Card Names So We Made this Card to Have the Absolute Longest Card Name Ever Elemental", 
+                "OurMarketResearchShowsThatPlayersLikeReallyLongCardNamesSoWeMadethisCardtoHavetheAbsoluteLongestCardNameEverElemental"),
+            ("Mr. Mime", "Mr.Mime"),
+            ("Mr.Mime", "Mr.Mime"),
+            ("ピカチュウ", "ピカチュウ"),
+            ("1+Base+Mr. Mime+0.png", "1+Base+Mr.Mime+0.png"),
+            ("12+Alpha+Æther Vial+3.jpg", "12+Alpha+AetherVial+3.jpg")
+        ];
+        for (name, expected) in names.iter() {
+            assert_eq!(format_text_for_fs(name), *expected, "formatting of {}", name);
+        }
+    }
+
+    #[test]
+    fn format_text_for_fs_handles_reserved_names() {
+        let names = [
+            ("CON", "CON_"),
+            ("con.jpg", "con_.jpg"),
+            ("Aux.Tail.png", "Aux_.Tail.png"),
+            ("LPT1", "LPT1_"),
+            ("Console", "Console"),
+            ("...", "_"),
+            ("", "_"),
+            (".hidden.png", "hidden.png"),
+            ("Trailing...", "Trailing"),
+            ("a<b>c?d*e\u{7}.png", "abcde.png")
+        ];
+        for (name, expected) in names.iter() {
+            assert_eq!(format_text_for_fs(name), *expected, "formatting of {}", name);
+        }
+    }
+
+    #[test]
+    fn format_text_for_fs_limits_length() {
+        let long_name = "Ω".repeat(200);
+        let first = format_text_for_fs(&format!("{}+0.jpg", long_name));
+        let second = format_text_for_fs(&format!("{}+1.jpg", long_name));
+        assert!(first.len() <= MAX_FILENAME_LEN);
+        assert!(first.ends_with(".jpg"));
+        assert_ne!(first, second);
+        assert_eq!(first, format_text_for_fs(&format!("{}+0.jpg", long_name)));
+    }
+
+    #[test]
+    fn get_unique_filename_appends_number() {
+        let taken = ["image.png", "image-1.png", "noext"];
+        let is_taken = |filename: &str| taken.contains(&filename);
+        assert_eq!(get_unique_filename("other.png", is_taken), "other.png");
+        assert_eq!(get_unique_filename("image.png", is_taken), "image-2.png");
+        assert_eq!(get_unique_filename("noext", is_taken), "noext-1");
+        assert_eq!(get_unique_filename("Mr.Mime", |filename| filename == "Mr.Mime"), "Mr.Mime-1");
+    }
+
+    #[test]
+    fn split_extension_accepts_image_extensions_only() {
+        let filenames = [
+            ("Mr.Mime", ("Mr.Mime", None)),
+            ("1+Base+Mr.Mime+0.png", ("1+Base+Mr.Mime+0", Some("png"))),
+            ("scan.JPEG", ("scan", Some("JPEG"))),
+            ("notes.txt", ("notes.txt", None)),
+            (".png", (".png", None))
+        ];
+        for (filename, expected) in filenames.iter() {
+            assert_eq!(split_extension(filename), *expected, "splitting of {}", filename);
+        }
+    }
+
+    #[test]
+    fn parse_index_from_filename_handles_any_width() {
+        assert_eq!(parse_index_from_filename("1+Base+Pikachu+3.png"), Some(3));
+        assert_eq!(parse_index_from_filename("1+Base+Pikachu+123.png"), Some(123));
+        assert_eq!(parse_index_from_filename("Pikachu_IMG_FRONT.png"), None);
+        assert_eq!(parse_index_from_filename("Pikachu7"), Some(7));
+    }
 }
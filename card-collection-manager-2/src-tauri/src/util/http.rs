@@ -0,0 +1,244 @@
+use std::io::{Read, Write};
+use std::fs::File;
+use std::path::Path;
+use std::thread::sleep;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::sync::atomic::{AtomicU64, Ordering};
+use serde::de::DeserializeOwned;
+use reqwest::StatusCode;
+use reqwest::blocking::{Client, Response};
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT, RETRY_AFTER, ETAG, LAST_MODIFIED};
+
+use super::config::{HttpConfiguration, load_configuration};
+use super::jobs::Job;
+
+/// Point in time of the last request in milliseconds since the unix epoch,
+/// used to keep the configured minimum delay between two requests.
+static LAST_REQUEST_MS: AtomicU64 = AtomicU64::new(0);
+
+/// Perform a GET request to the provided URL with the shared HTTP client configuration.
+/// Requests are rate limited according to the configured minimum request interval, and 
+/// requests that fail due to connection errors, timeouts, rate limits (`429`) or server errors 
+/// (`5xx`) are retried with an exponential backoff. Other client errors (`4xx`) are not retried.
+/// 
+/// # Arguments
+/// `url`       -   URL of the resource that should be requested
+/// `query`     -   Query parameters that should be appended to the URL
+/// `headers`   -   Additional request headers, e.g. API keys
+/// 
+/// # Returns
+/// The response of the request. Note that responses with status `304 Not Modified` are
+/// returned as well, since they are the expected result of conditional requests.
+/// 
+pub fn get<'a>(url: &str, query: &[(&str, String)], headers: &[(&str, &str)]) -> Result<Response, &'a str> {
+    let config: HttpConfiguration = load_configuration().unwrap().http;
+    let client: Client = build_client(&config)?;
+
+    let mut attempt: u32 = 0;
+    loop {
+        wait_for_request_slot(&config);
+
+        let mut request = client.get(url).query(query);
+        for (name, value) in headers.iter() {
+            request = request.header(*name, *value);
+        }
+
+        let mut retry_after: Option<Duration> = None;
+        match request.send() {
+            Ok(resp) => {
+                let status = resp.status();
+                if !(status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()) {
+                    return match resp.error_for_status() {
+                        Ok(resp) => Ok(resp),
+                        Err(_) => Err("Request was rejected by the server.")
+                    };
+                }
+                retry_after = resp.headers().get(RETRY_AFTER)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.parse::<u64>().ok())
+                    .map(Duration::from_secs);
+            },
+            Err(err) => {
+                if !(err.is_timeout() || err.is_connect() || err.is_request()) {
+                    return Err("Unable to perform request.");
+                }
+            }
+        }
+
+        if attempt >= config.retries {
+            return Err("Unable to perform request, maximum number of retries exceeded.");
+        }
+        let backoff = Duration::from_millis(config.retry_backoff_ms.saturating_mul(2u64.saturating_pow(attempt)));
+        sleep(retry_after.unwrap_or(backoff));
+        attempt += 1;
+    }
+}
+
+/// Perform a GET request to the provided URL (see function `get`) and deserialize the
+/// JSON response body into an instance of type `T`.
+/// 
+/// # Arguments
+/// `url`       -   URL of the resource that should be requested
+/// `query`     -   Query parameters that should be appended to the URL
+/// `headers`   -   Additional request headers, e.g. API keys
+/// 
+pub fn get_json<'a, T: DeserializeOwned>(url: &str, query: &[(&str, String)], headers: &[(&str, &str)]) -> Result<T, &'a str> {
+    match get(url, query, headers)?.json::<T>() {
+        Ok(data) => Ok(data),
+        Err(_) => Err("Unable to deserialize response.")
+    }
+}
+
+/// Perform a GET request to the provided URL (see function `get`) and return the raw 
+/// response body.
+/// 
+/// # Arguments
+/// `url`   -   URL of the resource that should be requested
+/// 
+pub fn get_bytes<'a>(url: &str) -> Result<Vec<u8>, &'a str> {
+    match get(url, &[], &[])?.bytes() {
+        Ok(data) => Ok(data.to_vec()),
+        Err(_) => Err("Unable to read response.")
+    }
+}
+
+/// Perform a GET request to the provided URL (see function `get`) and stream the response body
+/// into the provided file. The configured timeout applies to each read of the body instead of the
+/// whole download, so large files can be downloaded as well. The download progress is reported
+/// to the provided job, which can cancel the download.
+/// 
+/// # Arguments
+/// `url`       -   URL of the resource that should be downloaded
+/// `target`    -   Path of the file the resource should be written to
+/// `job`       -   Job that runs the download
+/// 
+pub fn download<'a>(url: &str, target: &Path, job: &Job) -> Result<(), &'a str> {
+    let mut resp: Response = get(url, &[], &[])?;
+    let total: Option<u64> = resp.content_length();
+    let mut file = match File::create(target) {
+        Ok(file) => file,
+        Err(_) => return Err("Unable to create download file.")
+    };
+
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut downloaded: u64 = 0;
+    let mut reported_percent: u64 = 0;
+    loop {
+        job.check_cancelled()?;
+        let read = match resp.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(_) => return Err("Unable to read response.")
+        };
+        if file.write_all(&buffer[..read]).is_err() {
+            return Err("Unable to write download file.");
+        }
+        downloaded += read as u64;
+
+        // only report whole percents to avoid flooding the job listeners
+        if let Some(total) = total.filter(|total| *total > 0) {
+            let percent = downloaded * 100 / total;
+            if percent > reported_percent {
+                reported_percent = percent;
+                job.report_progress(percent as f32 / 100.0, "Downloading");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Build a blocking HTTP client according to the provided configuration.
+fn build_client<'a>(config: &HttpConfiguration) -> Result<Client, &'a str> {
+    let mut default_headers = HeaderMap::new();
+    default_headers.insert(ACCEPT, HeaderValue::from_static("application/json;q=0.9,*/*;q=0.8"));
+
+    let user_agent: String = match &config.user_agent {
+        Some(user_agent) if !user_agent.is_empty() => user_agent.clone(),
+        _ => format!("{}/{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+    };
+
+    let mut builder = Client::builder()
+        .timeout(Duration::from_secs(config.timeout_secs))
+        .user_agent(user_agent)
+        .default_headers(default_headers);
+
+    if let Some(proxy) = config.proxy.as_ref().filter(|proxy| !proxy.is_empty()) {
+        match reqwest::Proxy::all(proxy) {
+            Ok(proxy) => builder = builder.proxy(proxy),
+            Err(_) => return Err("Invalid proxy URL.")
+        }
+    }
+
+    match builder.build() {
+        Ok(client) => Ok(client),
+        Err(_) => Err("Unable to build HTTP client.")
+    }
+}
+
+/// Block until the configured minimum delay since the last request has passed and
+/// reserve the current point in time for the next request.
+fn wait_for_request_slot(config: &HttpConfiguration) {
+    loop {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+        let last = LAST_REQUEST_MS.load(Ordering::SeqCst);
+        let next = last.saturating_add(config.request_interval_ms);
+        if now < next {
+            sleep(Duration::from_millis(next - now));
+            continue;
+        }
+        if LAST_REQUEST_MS.compare_exchange(last, now, Ordering::SeqCst, Ordering::SeqCst).is_ok() {
+            return;
+        }
+    }
+}
+
+/// Get the cache validators of the provided response, i.e. the values of its `ETag` and
+/// `Last-Modified` headers, which can be used for conditional requests later on.
+/// 
+/// # Returns
+/// Tuple of the `ETag` and the `Last-Modified` header values, if present
+/// 
+pub fn get_cache_validators(resp: &Response) -> (Option<String>, Option<String>) {
+    let get_header = |name| resp.headers().get(name)
+        .and_then(|value: &HeaderValue| value.to_str().ok())
+        .map(String::from);
+    (get_header(ETAG), get_header(LAST_MODIFIED))
+}
+
+/// Build the request headers for a conditional request from the provided cache validators.
+/// 
+/// # Arguments
+/// `etag`          -   `ETag` header value of a previous response
+/// `last_modified` -   `Last-Modified` header value of a previous response
+/// 
+pub fn get_conditional_headers<'b>(etag: &'b Option<String>, last_modified: &'b Option<String>) -> Vec<(&'static str, &'b str)> {
+    let mut headers: Vec<(&'static str, &'b str)> = Vec::new();
+    if let Some(etag) = etag {
+        headers.push(("If-None-Match", etag));
+    }
+    if let Some(last_modified) = last_modified {
+        headers.push(("If-Modified-Since", last_modified));
+    }
+    headers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_conditional_headers_uses_all_validators() {
+        let etag: Option<String> = Some(String::from("\"abc\""));
+        let last_modified: Option<String> = Some(String::from("Wed, 21 Oct 2015 07:28:00 GMT"));
+        assert_eq!(get_conditional_headers(&etag, &last_modified), vec![
+            ("If-None-Match", "\"abc\""),
+            ("If-Modified-Since", "Wed, 21 Oct 2015 07:28:00 GMT")
+        ]);
+        assert_eq!(get_conditional_headers(&etag, &None), vec![("If-None-Match", "\"abc\"")]);
+    }
+
+    #[test]
+    fn get_conditional_headers_without_validators_requests_unconditionally() {
+        assert!(get_conditional_headers(&None, &None).is_empty());
+    }
+}
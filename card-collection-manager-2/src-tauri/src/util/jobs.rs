@@ -0,0 +1,179 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use serde::{Serialize, Deserialize};
+use tauri::{AppHandle, Manager, State};
+
+/// Event that is emitted whenever a running job reports progress.
+pub const JOB_PROGRESS_EVENT: &str = "job://progress";
+/// Event that is emitted when a job has finished, failed or was cancelled.
+pub const JOB_FINISHED_EVENT: &str = "job://finished";
+
+/// Status of a running job, as it is listed and emitted to the frontend.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JobStatus {
+    /// id of the job
+    pub id: u32,
+    /// kind of the job, e.g. `update_sets`
+    pub kind: String,
+    /// progress of the job between `0.0` and `1.0`
+    pub progress: f32,
+    /// description of the current step of the job
+    pub message: String
+}
+
+/// Outcome of a job, as it is emitted to the frontend when the job has ended.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JobOutcome {
+    /// id of the job
+    pub id: u32,
+    /// kind of the job, e.g. `update_sets`
+    pub kind: String,
+    /// result of the job as JSON string, if it was successful
+    pub result: Option<String>,
+    /// error message, if the job failed
+    pub error: Option<String>,
+    /// flag to indicate that the job was cancelled
+    pub cancelled: bool
+}
+
+/// Registry of all running jobs, managed as app state.
+#[derive(Default)]
+pub struct JobRegistry {
+    next_id: AtomicU32,
+    jobs: Mutex<HashMap<u32, (JobStatus, Arc<AtomicBool>)>>
+}
+
+/// Handle that is passed to the task of a job, in order to report progress and to check for
+/// cancellation. Tasks should check for cancellation between their steps and stop as soon as
+/// possible, without persisting any partial results.
+pub struct Job {
+    cancelled: Arc<AtomicBool>,
+    reporter: Option<Box<dyn Fn(f32, &str) + Send + Sync>>
+}
+
+impl Job {
+    /// Create a job handle that is not registered, e.g. to run a job's task synchronously.
+    /// It never gets cancelled and does not report any progress.
+    pub fn detached() -> Job {
+        Job { cancelled: Arc::new(AtomicBool::new(false)), reporter: None }
+    }
+
+    /// Report the progress of the job, between `0.0` and `1.0`, together with a description
+    /// of the current step.
+    pub fn report_progress(&self, progress: f32, message: &str) {
+        if let Some(reporter) = &self.reporter {
+            reporter(progress.max(0.0).min(1.0), message);
+        }
+    }
+
+    /// Check whether the job was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Return an error if the job was cancelled, so tasks can stop via the `?` operator.
+    pub fn check_cancelled<'a>(&self) -> Result<(), &'a str> {
+        match self.is_cancelled() {
+            true => Err("Job was cancelled."),
+            false => Ok(())
+        }
+    }
+}
+
+/// Start the provided task as a job in the background and return the id of the job. While the job 
+/// is running, it is listed by `get_running_jobs_json` and can be cancelled via `cancel_job`.
+/// Progress is emitted as `job://progress` event, the outcome as `job://finished` event. A task that
+/// panics ends its job with an error as well.
+/// 
+/// # Arguments
+/// `app`   -   Handle of the app, which manages the `JobRegistry`
+/// `kind`  -   Kind of the job, e.g. `update_sets`
+/// `task`  -   Task of the job, which returns its result as JSON string
+/// 
+/// # Returns
+/// Id of the new job
+/// 
+pub fn start_job<F>(app: &AppHandle, kind: &str, task: F) -> u32 
+where F: FnOnce(&Job) -> Result<String, String> + Send + 'static {
+    let registry: State<JobRegistry> = app.state::<JobRegistry>();
+    let id: u32 = registry.next_id.fetch_add(1, Ordering::SeqCst);
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let status = JobStatus { id: id, kind: kind.to_string(), progress: 0.0, message: String::new() };
+    registry.jobs.lock().unwrap().insert(id, (status, cancelled.clone()));
+
+    let app = app.clone();
+    let kind = kind.to_string();
+    tauri::async_runtime::spawn_blocking(move || {
+        let reporter_app = app.clone();
+        let job = Job {
+            cancelled: cancelled,
+            reporter: Some(Box::new(move |progress: f32, message: &str| {
+                let registry: State<JobRegistry> = reporter_app.state::<JobRegistry>();
+                let status: Option<JobStatus> = registry.jobs.lock().unwrap().get_mut(&id).map(|(status, _)| {
+                    status.progress = progress;
+                    status.message = message.to_string();
+                    status.clone()
+                });
+                if let Some(status) = status {
+                    let _ = reporter_app.emit_all(JOB_PROGRESS_EVENT, status);
+                }
+            }))
+        };
+
+        let result: Result<String, String> = match catch_unwind(AssertUnwindSafe(|| task(&job))) {
+            Ok(result) => result,
+            Err(panic) => Err(get_panic_message(panic.as_ref()))
+        };
+
+        let registry: State<JobRegistry> = app.state::<JobRegistry>();
+        registry.jobs.lock().unwrap().remove(&id);
+        let cancelled = job.is_cancelled();
+        let outcome = match result {
+            Ok(result) => JobOutcome { id: id, kind: kind, result: Some(result), error: None, cancelled: cancelled },
+            Err(error) => JobOutcome { id: id, kind: kind, result: None, error: Some(error), cancelled: cancelled }
+        };
+        let _ = app.emit_all(JOB_FINISHED_EVENT, outcome);
+    });
+    id
+}
+
+/// Get the message of a panic, as it is reported as error of the job whose task panicked.
+fn get_panic_message(panic: &(dyn Any + Send)) -> String {
+    match panic.downcast_ref::<&str>() {
+        Some(message) => message.to_string(),
+        None => match panic.downcast_ref::<String>() {
+            Some(message) => message.clone(),
+            None => String::from("Job failed unexpectedly.")
+        }
+    }
+}
+
+/// Get the status of all running jobs as a JSON list, sorted by job id.
+#[tauri::command]
+pub fn get_running_jobs_json(registry: State<JobRegistry>) -> Result<String, String> {
+    let mut jobs: Vec<JobStatus> = registry.jobs.lock().unwrap()
+        .values()
+        .map(|(status, _)| status.clone())
+        .collect();
+    jobs.sort_by_key(|status| status.id);
+    Ok(serde_json::to_string(&jobs).unwrap())
+}
+
+/// Request the cancellation of the running job with the provided id.
+/// 
+/// # Argument
+/// * `id` - Id of the job that should be cancelled.
+/// 
+#[tauri::command]
+pub fn cancel_job(id: u32, registry: State<JobRegistry>) -> Result<(), String> {
+    match registry.jobs.lock().unwrap().get(&id) {
+        Some((_, cancelled)) => {
+            cancelled.store(true, Ordering::SeqCst);
+            Ok(())
+        },
+        None => Err(String::from("Unable to find running job."))
+    }
+}
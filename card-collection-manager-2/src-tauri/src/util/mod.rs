@@ -1,4 +1,7 @@
 pub mod enums;
 pub mod config;
 pub mod collection;
-pub mod fs;
\ No newline at end of file
+pub mod fs;
+pub mod http;
+pub mod jobs;
+pub mod protocol;
\ No newline at end of file
@@ -0,0 +1,131 @@
+use std::error::Error;
+use std::fs::read;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tauri::AppHandle;
+use tauri::http::{Request, Response, ResponseBuilder};
+
+use crate::templates::card_service_templates::get_entry_image_path;
+use crate::templates::thumbnail_templates::find_thumbnail_path;
+
+/// URI scheme under which the app serves local files to the frontend, e.g. `ccm://magic/images/<name>`.
+pub const URI_SCHEME: &str = "ccm";
+
+/// Handle a request of the URI scheme `ccm`. Images of the collections are requested as
+/// `ccm://<game>/images/<name>` and returned with their original bytes. Thumbnails of the images
+/// are requested as `ccm://<game>/thumbnails/<size>/<name>` and generated in the background on demand. Until
+/// a thumbnail exists, the image itself is returned instead. Since some platforms
+/// serve custom schemes as `https://ccm.localhost/<path>` and `convertFileSrc` of the frontend
+/// encodes the path as `ccm://localhost/<encoded path>`, these forms are supported as well.
+///
+/// Responses carry an `ETag` based on the size and modification time of the file and must be
+/// revalidated, so images that were changed in place are never served from a stale cache.
+///
+/// # Arguments
+/// `_app`      -   Handle of the app
+/// `request`   -   Request of the webview
+///
+pub fn handle_request(_app: &AppHandle, request: &Request) -> Result<Response, Box<dyn Error>> {
+    let path: PathBuf = match parse_image_uri(request.uri()) {
+        Some((game, image, None)) => match get_entry_image_path(&game, &image) {
+            Ok(path) => path,
+            Err(_) => return ResponseBuilder::new().status(400).body(Vec::new())
+        },
+        Some((game, image, Some(size))) => match find_thumbnail_path(&game, &image, size) {
+            Ok(Some(path)) => path,
+            Ok(None) => match get_entry_image_path(&game, &image) {
+                Ok(path) => path,
+                Err(_) => return ResponseBuilder::new().status(400).body(Vec::new())
+            },
+            Err(_) => return ResponseBuilder::new().status(404).body(Vec::new())
+        },
+        None => return ResponseBuilder::new().status(400).body(Vec::new())
+    };
+
+    let etag: String = match get_etag(&path) {
+        Some(etag) => etag,
+        None => return ResponseBuilder::new().status(404).body(Vec::new())
+    };
+    let if_none_match = request.headers().get("If-None-Match").and_then(|value| value.to_str().ok());
+    if if_none_match == Some(etag.as_str()) {
+        return ResponseBuilder::new()
+            .status(304)
+            .header("ETag", etag.as_str())
+            .header("Cache-Control", "no-cache")
+            .body(Vec::new());
+    }
+
+    let data: Vec<u8> = read(&path)?;
+    ResponseBuilder::new()
+        .status(200)
+        .mimetype(get_mime_type(&path))
+        .header("ETag", etag.as_str())
+        .header("Cache-Control", "no-cache")
+        .body(data)
+}
+
+/// Parse the game, image name and thumbnail size from the provided URI, see `handle_request`.
+///
+/// # Returns
+/// Tuple of the game name, e.g. `magic`, the image name and the thumbnail size, if a thumbnail is requested
+///
+fn parse_image_uri(uri: &str) -> Option<(String, String, Option<u32>)> {
+    let uri: &str = uri.split_once("://").map_or(uri, |(_, rest)| rest);
+    let uri: &str = uri.split(['?', '#']).next().unwrap_or_default();
+    let uri: String = percent_decode(uri)?;
+
+    let mut segments: Vec<&str> = uri.split('/').filter(|segment| !segment.is_empty()).collect();
+    if segments.first().map_or(false, |host| *host == "localhost" || *host == format!("{}.localhost", URI_SCHEME)) {
+        segments.remove(0);
+    }
+    let (game, image, size) = match segments.as_slice() {
+        [game, "images", image] => (game, image, None),
+        [game, "thumbnails", size, image] => (game, image, Some(size.parse::<u32>().ok()?)),
+        _ => return None
+    };
+    match game.to_lowercase().as_str() {
+        game @ ("magic" | "pokemon") => Some((game.to_string(), image.to_string(), size)),
+        _ => None
+    }
+}
+
+/// Decode all percent-encoded characters of the provided text, e.g. `%2F` to `/`.
+fn percent_decode(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut decoded: Vec<u8> = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        }
+        else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// Get the entity tag of the provided file, based on its size and modification time.
+fn get_etag(path: &Path) -> Option<String> {
+    let metadata = path.metadata().ok().filter(|metadata| metadata.is_file())?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some(format!("\"{:x}-{:x}\"", metadata.len(), modified.as_nanos()))
+}
+
+/// Get the MIME type of the provided file based on its extension.
+fn get_mime_type(path: &Path) -> &'static str {
+    let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+    match extension.to_lowercase().as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "bmp" => "image/bmp",
+        "tif" | "tiff" => "image/tiff",
+        "svg" => "image/svg+xml",
+        _ => "application/octet-stream"
+    }
+}
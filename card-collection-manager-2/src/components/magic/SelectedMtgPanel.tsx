@@ -47,6 +47,7 @@ const SelectedMtgPanel: React.FC<{
 
   return (
     <EntryPanelTemplate
+      game="Magic"
       entry={props.entry}
       defaultImageUrl="https://gamepedia.cursecdn.com/mtgsalvation_gamepedia/f/f8/Magic_card_back.jpg"
       extraAttributes={extraAttributes}
@@ -59,6 +59,7 @@ const SelectedPokemonPanel: React.FC<{
 
   return (
     <EntryPanelTemplate
+      game="Pokemon"
       entry={props.entry}
       defaultImageUrl="https://archives.bulbagarden.net/media/upload/1/17/Cardback.jpg"
       extraAttributes={extraAttributes}
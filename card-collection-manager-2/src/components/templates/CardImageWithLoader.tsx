@@ -1,74 +1,55 @@
-import React, { Suspense } from "react";
-import { invoke } from "@tauri-apps/api/tauri";
-import Image from "next/image";
+import React, { useEffect, useState } from "react";
+import { convertFileSrc } from "@tauri-apps/api/tauri";
 
 import SpinnerLoader from "./SpinnerLoader";
 
-// Promise wrapper that acts as an interface for a component to work with React.Suspense.
-const wrapPromise = <T,>(promise: Promise<T>) => {
-  let status = 'pending'; // set initial status
-  let result: T;
-  let error: any;
-
-  // resolve promise
-  let suspender = promise.then(
-    (res) => {
-      status = 'success';
-      result = res;
-    },
-    (err) => {
-      status = 'error';
-      error = err;
-    }
-  );
-
-  return {
-    read() {
-      switch (status) {
-        case 'pending': throw suspender;
-        case 'error': throw error;
-        default: return result;
-      }
-    }
-  };
+// Get the URL of an image within the image directory of a game. The images are served
+// by the backend via the custom URI scheme `ccm`, e.g. `ccm://magic/images/<name>`.
+export const getImageUrl = (game: string, image: string) => {
+  return convertFileSrc(`${game.toLowerCase()}/images/${image}`, "ccm");
 };
 
-// Get the promise that returns the image data as base-64 string once it resolves.
-const loadImagePromise = (images: string[], index: number, game: string) => {
-    return invoke("get_image_b64", { image: images[index], game: game})
-    .then(res => res as string)
-    .catch(err => err)
-  }
-
-  // wrap image data promise inside the "Suspense interface"
-const loadImageWrapped = (images: string[], index: number, game: string) => {
-  const imagePromise = loadImagePromise(images, index, game);
-  return wrapPromise(imagePromise);
+// Get the URL of the thumbnail of an image within the image directory of a game. The backend
+// generates the thumbnail on demand with the configured size that is closest to the requested size.
+export const getThumbnailUrl = (game: string, image: string, size: number) => {
+  return convertFileSrc(`${game.toLowerCase()}/thumbnails/${size}/${image}`, "ccm");
 };
 
-// Image component that can be used with React.Suspense 
-const CardImageSuspendable: React.FC<{ resource: any }> = (props) => {
-  const imageB64: string = props.resource.read();
-  return (
-    <Image src={imageB64} layout="fill" objectFit="contain" />
-  )
+// Get the images of an entry in the order they should be displayed, i.e. the primary image first.
+export const getDisplayImages = (images: string[], primaryImage?: string) => {
+  if (!primaryImage || !images.includes(primaryImage)) {
+    return images;
+  }
+  return [primaryImage].concat(images.filter((image) => image != primaryImage));
 };
 
 /**
- * This component loads an image from the backend and displays it. It uses React.Suspense in order
- * to display a loading animation until the image data is loaded.
- * 
+ * This component loads an image from the backend and displays it. It displays a loading
+ * animation until the image data is loaded.
+ *
  * # Props:
  * * game       - Name of the game for which images should be displayed.
  * * images     - List of image names. From this list, one image is loaded and displayed at a time.
- * * index      - index that indicates which element from `props.images` should be displayed. 
+ * * index      - index that indicates which element from `props.images` should be displayed.
  */
 const CardImageWithLoader: React.FC<{images: string[], index: number, game: string}> = (props) => {
+  const [loaded, setLoaded] = useState<boolean>(false);
+  const imageUrl = getImageUrl(props.game, props.images[props.index]);
+
+  // display the loading animation again each time another image is selected
+  useEffect(() => {
+    setLoaded(false);
+  }, [imageUrl]);
 
   return (
-      <Suspense fallback={<SpinnerLoader/>}>
-        <CardImageSuspendable resource={loadImageWrapped(props.images, props.index, props.game)} />
-      </Suspense>
+      <>
+        {loaded ? "" : <SpinnerLoader/>}
+        <img
+          src={imageUrl}
+          className={`absolute inset-0 w-full h-full object-contain ${loaded ? "" : "invisible"}`}
+          onLoad={() => setLoaded(true)}
+        />
+      </>
   );
 };
 
@@ -7,17 +7,21 @@ import React, {
 } from "react";
 
 import ModalTemplate from "../templates/ModalTemplate";
+import { getDisplayImages, getThumbnailUrl } from "../templates/CardImageWithLoader";
 
-import { SetTemplate, EntryTemplate } from "../../types";
+import { SetTemplate, EntryTemplate, CatalogMatch } from "../../types";
 
 import { invoke } from "@tauri-apps/api/tauri";
 import { open } from "@tauri-apps/api/dialog";
 
-import { VscClose } from "react-icons/vsc";
+import { VscArrowDown, VscArrowUp, VscClose, VscStarEmpty, VscStarFull } from "react-icons/vsc";
 
 import IntegerInput from "../templates/IntegerInput";
 import ConfirmationModal from "../modals/ConfirmationModal";
 
+// Delay in milliseconds after the last keystroke, before the card catalog is searched.
+const SEARCH_DELAY = 300;
+
 // Enum to control wether the modal is in "Create" or "Edit" mode.
 export enum Mode {
   Create,
@@ -102,6 +106,18 @@ const CreateEditModalTemplate: React.FC<{
 
   // images as state variable for better handling
   const [images, setImages] = useState<string[]>([]);
+  // roles of the images, e.g. front or back, and the image that is displayed first
+  const [imageRoles, setImageRoles] = useState<{ [image: string]: string }>({});
+  const [primaryImage, setPrimaryImage] = useState<string>(null);
+  // link of the entry to its card identity, which is removed by setting it to null
+  const [upstreamId, setUpstreamId] = useState<string | null>(null);
+  const [imageRoleVariants, setImageRoleVariants] = useState<string[]>([]);
+
+  // card name suggestions from the card catalog of the selected set
+  const [suggestions, setSuggestions] = useState<CatalogMatch[]>([]);
+  // pending catalog search and number of the latest search, so results of outdated searches are ignored
+  const searchTimeout = useRef<ReturnType<typeof setTimeout>>();
+  const searchCount = useRef(0);
 
   // The first time this modal gets rendered, it fetches language and condition
   // informations from the backened.
@@ -115,6 +131,11 @@ const CreateEditModalTemplate: React.FC<{
       const obj = JSON.parse(result as string) as string[];
       setConditions(obj);
     });
+
+    invoke("get_image_role_variants_json").then((result) => {
+      const obj = JSON.parse(result as string) as string[];
+      setImageRoleVariants(obj);
+    });
   }, []);
 
   // everytime the modal becomes visible in "Edit" mode, it populates
@@ -145,27 +166,35 @@ const CreateEditModalTemplate: React.FC<{
         props.extraAttributes.map(attribute => extraAttributesRefs[attribute.accessKey].current!.checked = props.selectedEntry[attribute.accessKey]);
 
         setImages(props.selectedEntry.images);
+        setImageRoles(props.selectedEntry.imageRoles ?? {});
+        setPrimaryImage(props.selectedEntry.primaryImage ?? null);
+        setUpstreamId(props.selectedEntry.upstreamId ?? null);
       }
     } else {
-      // clear image state in any case the modal gets closed
+      // clear image and suggestion state in any case the modal gets closed
+      clearTimeout(searchTimeout.current);
       setImages([]);
+      setImageRoles({});
+      setPrimaryImage(null);
+      setUpstreamId(null);
+      setSuggestions([]);
     }
   }, [props.visible]);
 
   // handler when user aborts maintaining an entry by clicking the close icon
   const onClose = () => {
-    // if "Create" mode, delete all temporary stored images
+    // if "Create" mode, discard all temporary stored images
     if (props.mode == Mode.Create) {
       images.forEach((image) => {
-        invoke("delete_image", { image: image, game: props.game });
+        invoke("discard_image", { image: image, game: props.game });
       });
     }
-    // if "Edit" mode, delete all newly added images
+    // if "Edit" mode, discard all newly added images
     if (props.mode == Mode.Edit) {
       images
         .filter((image) => !props.selectedEntry.images.includes(image))
         .forEach((image) => {
-          invoke("delete_image", { image: image, game: props.game });
+          invoke("discard_image", { image: image, game: props.game });
         });
     }
     props.setVisible(false);
@@ -185,12 +214,64 @@ const CreateEditModalTemplate: React.FC<{
       signed: signedRef.current!.checked,
       note: noteRef.current!.value,
       images: images,
+      imageRoles: imageRoles,
+      primaryImage: primaryImage ?? undefined,
+      // the identity link is only sent when editing, so that an unlinked entry is sent with null
+      upstreamId: props.mode == Mode.Edit ? upstreamId : undefined,
     };
     // get values of extra attributes
     props.extraAttributes.map(attribute => cardEntry[attribute.accessKey] = extraAttributesRefs[attribute.accessKey].current!.checked);
     return cardEntry;
   };
 
+  // search the card catalog of the selected set for card names matching the current name input, once
+  // the user stopped typing. If the name matches a suggestion exactly, the set number is filled in as well.
+  const searchCatalog = async () => {
+    const query = nameRef.current!.value;
+    const match = suggestions.find((suggestion) => suggestion.name === query && suggestion.setId === setRef.current!.value);
+    if (match && setNoRef.current!.value === "") {
+      setNoRef.current!.value = match.number;
+    }
+    clearTimeout(searchTimeout.current);
+    const search = ++searchCount.current;
+    if (query.trim().length < 2) {
+      setSuggestions([]);
+      return;
+    }
+    searchTimeout.current = setTimeout(() => {
+      invoke("search_catalog", { query: query, setId: setRef.current!.value, limit: 10, game: props.game })
+        .then((result) => {
+          if (search === searchCount.current) {
+            setSuggestions(JSON.parse(result as string) as CatalogMatch[]);
+          }
+        })
+        .catch(() => {
+          if (search === searchCount.current) {
+            setSuggestions([]);
+          }
+        });
+    }, SEARCH_DELAY);
+  };
+
+  // if the name input contains a set id or code and a collector number, e.g. "DRI 4" or "base1-4",
+  // look up the card and fill in its name, set and set number.
+  const lookupCard = async () => {
+    const reference = nameRef.current!.value.trim().match(/^(\S+)[\s#-]+(\S*\d\S*)$/);
+    if (!reference) {
+      return;
+    }
+    invoke("lookup_card", { set: reference[1], number: reference[2], game: props.game })
+      .then((result) => {
+        const match = JSON.parse(result as string) as CatalogMatch | null;
+        if (match) {
+          nameRef.current!.value = match.name;
+          setRef.current!.value = match.setId;
+          setNoRef.current!.value = match.number;
+        }
+      })
+      .catch((reject) => console.log(reject));
+  };
+
   // submit an entry to the backend based on the current values of all input fields.
   // if "Edit" mode, the existing entry will be overwritten.
   const submitEntry = async (event: React.FormEvent<HTMLFormElement>) => {
@@ -210,7 +291,9 @@ const CreateEditModalTemplate: React.FC<{
     if (props.mode == Mode.Edit) {
       cardEntry.id = props.selectedEntry.id;
       invoke("update_card", { obj: JSON.stringify(cardEntry), game: props.game })
-        .then(() => {
+        .then((result) => {
+          // the backend renames the images of the entry according to the naming scheme
+          cardEntry = JSON.parse(result as string);
           props.setCollection(
             props.collection.map((entry) =>
               entry.id == cardEntry.id ? cardEntry : entry
@@ -247,7 +330,10 @@ const CreateEditModalTemplate: React.FC<{
                           newEntry: props.mode == Mode.Create,
                           game: props.game
                         });
-          cardEntry.images.push(imgId as string);
+          // identical images are shared, so an image that the entry already uses is not added twice
+          if (!cardEntry.images.includes(imgId as string)) {
+            cardEntry.images.push(imgId as string);
+          }
         }
         setImages([].concat(cardEntry.images)); // using the concat with an empty array is a trick to trigger the rerender, otherwise the state does recognize to rerender
 
@@ -257,18 +343,52 @@ const CreateEditModalTemplate: React.FC<{
 
   // Enable the connected image modal and start the display by the provided image index.
   const displayImage = async (index: number) => {
-    props.setImageModalImages(images);
-    props.setImageModalImageIndex(index);
+    const displayImages = getDisplayImages(images, primaryImage);
+    props.setImageModalImages(displayImages);
+    props.setImageModalImageIndex(displayImages.indexOf(images[index]));
     props.setImageModalVisible(true);
   };
 
-  // Delete the image with the specified image name by calling the corresponding backend service
-  // and remove the image name from the temporary list.
+  // Move the image with the specified index by the specified offset within the temporary list of images.
+  const moveImage = (index: number, offset: number) => {
+    const target = index + offset;
+    if (target < 0 || target >= images.length) {
+      return;
+    }
+    const reordered = [].concat(images);
+    [reordered[index], reordered[target]] = [reordered[target], reordered[index]];
+    setImages(reordered);
+  };
+
+  // Assign the specified role to the image with the specified name. An empty role removes the role.
+  const setImageRole = (image: string, role: string) => {
+    const roles = { ...imageRoles };
+    if (role === "") {
+      delete roles[image];
+    } else {
+      roles[image] = role;
+    }
+    setImageRoles(roles);
+  };
+
+  // Remove the image with the specified image name from the temporary list. Images of the stored entry
+  // are deleted by the backend, once the entry is saved, so that aborting the edit keeps them. Newly added
+  // images are discarded immediately by calling the corresponding backend service, which keeps images
+  // that saved entries share.
   const deleteImage = async (image: string) => {
-    invoke("delete_image", { image: image, game: props.game })
-      .then(() => {
-        setImages(images.filter((img) => img != image));
-      })
+    const removeImage = () => {
+      setImages(images.filter((img) => img != image));
+      setImageRole(image, "");
+      if (primaryImage == image) {
+        setPrimaryImage(null);
+      }
+    };
+    if (props.mode == Mode.Edit && props.selectedEntry.images.includes(image)) {
+      removeImage();
+      return;
+    }
+    invoke("discard_image", { image: image, game: props.game })
+      .then(removeImage)
       .catch((reject) => console.log(reject));
   };
 
@@ -289,8 +409,16 @@ const CreateEditModalTemplate: React.FC<{
                   type="text"
                   required={true}
                   autoFocus={true}
+                  list="catalog-suggestions"
+                  onChange={() => searchCatalog()}
+                  onBlur={() => lookupCard()}
                   ref={nameRef}
                 />
+                <datalist id="catalog-suggestions">
+                  {suggestions.map((suggestion) => (
+                    <option value={suggestion.name}>{`${suggestion.setId.toUpperCase()} ${suggestion.number}`}</option>
+                  ))}
+                </datalist>
 
                 <label className="text-sm col-span-1">Set</label>
                 <select className="text-sm col-span-7 border-2" ref={setRef}>
@@ -307,6 +435,20 @@ const CreateEditModalTemplate: React.FC<{
                 />
                 <div className="col-span-5" />
 
+                {props.mode == Mode.Edit && upstreamId ? (
+                  <>
+                    <label className="text-sm col-span-1">Identity</label>
+                    <span className="text-sm col-span-5 truncate" title={upstreamId}>{upstreamId}</span>
+                    <button
+                      className="text-sm col-span-2 border-2"
+                      type="button"
+                      onClick={() => setUpstreamId(null)}
+                    >
+                      Unlink
+                    </button>
+                  </>
+                ) : null}
+
                 <label className="text-sm col-span-1">Language</label>
                 <select
                   className="text-sm col-span-3 border-2"
@@ -373,7 +515,30 @@ const CreateEditModalTemplate: React.FC<{
                         id={index.toString()}
                         onClick={() => displayImage(index)}
                       >
-                        Image {index + 1}
+                        <img src={getThumbnailUrl(props.game, value, 160)} alt={`Image ${index + 1}`} className="h-12 object-contain" />
+                      </div>
+                      <select
+                        className="mx-2 border-2"
+                        value={imageRoles[value] ?? ""}
+                        onChange={(e) => setImageRole(value, e.target.value)}
+                      >
+                        <option value="">No role</option>
+                        {imageRoleVariants.map((role) => (
+                          <option value={role}>{role}</option>
+                        ))}
+                      </select>
+                      <div
+                        className="mx-1 cursor-pointer hover:scale-125"
+                        title="Primary image"
+                        onClick={() => setPrimaryImage(primaryImage == value ? null : value)}
+                      >
+                        {primaryImage == value ? <VscStarFull className="text-yellow-500" /> : <VscStarEmpty />}
+                      </div>
+                      <div className="mx-1 cursor-pointer hover:scale-125" onClick={() => moveImage(index, -1)}>
+                        <VscArrowUp />
+                      </div>
+                      <div className="mx-1 cursor-pointer hover:scale-125" onClick={() => moveImage(index, 1)}>
+                        <VscArrowDown />
                       </div>
                       <div
                         className="mx-4 cursor-pointer hover:scale-125"
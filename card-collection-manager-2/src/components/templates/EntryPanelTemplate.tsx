@@ -1,6 +1,7 @@
 import React, { useEffect, useState } from "react";
 import { BsPencilFill, BsPaletteFill } from "react-icons/bs";
 import { EntryTemplate } from "../../types";
+import { getDisplayImages, getThumbnailUrl } from "./CardImageWithLoader";
 
 /**
  * Configuration object to specify additional, game-specific binary attributes of an entry.
@@ -19,6 +20,7 @@ type ExtraAttribute = {
  * that control this image modal.
  * 
  * # Props:
+ * * game                     - Name of the game to which the entry belongs.
  * * entry                    - entry object that contains the data that should be displayed.
  * * defaultImageUrl          - Url to the default image that should be displayed as a preview image of the entry 
  * * extraAttributes          - List of additional binary attributes beyond the standard binary attributes `signed` and `altered`
@@ -28,6 +30,7 @@ type ExtraAttribute = {
  * * setImageModalVisible     - Function to control the visiblity of the connected image modal. 
  **/
 const EntryPanelTemplate: React.FC<{
+  game: string;
   entry: EntryTemplate;
   defaultImageUrl: string;
   extraAttributes: ExtraAttribute[];
@@ -56,7 +59,7 @@ const EntryPanelTemplate: React.FC<{
   // trigger the connected image modal to display the image with the specfied index from
   // the list of images of the currently selected entry.
   const displayImage = async (index: number) => {
-    props.setImageModalImages(props.entry.images);
+    props.setImageModalImages(getDisplayImages(props.entry.images, props.entry.primaryImage));
     props.setImageModalImageIndex(index);
     props.setImageModalVisible(true);
   };
@@ -102,14 +105,15 @@ const EntryPanelTemplate: React.FC<{
             <p className="mt-4">{props.entry.note}</p>
 
             <p>Images</p>
-            <div>
-              {props.entry.images.map((value, index) => (
+            <div className="flex flex-wrap gap-2">
+              {getDisplayImages(props.entry.images, props.entry.primaryImage).map((value, index) => (
                 <div
                   className="hover:bg-blue-50 cursor-pointer"
                   id={index.toString()}
+                  title={props.entry.imageRoles?.[value]}
                   onClick={() => displayImage(index)}
                 >
-                  Image {index+1}
+                  <img src={getThumbnailUrl(props.game, value, 160)} alt={`Image ${index+1}`} className="h-16 object-contain" />
                 </div>
               ))}
             </div>
@@ -2,6 +2,7 @@ import { useEffect, useState } from "react";
 
 import { invoke } from "@tauri-apps/api/tauri";
 import { listen } from "@tauri-apps/api/event";
+import { open } from "@tauri-apps/api/dialog";
 
 import { VscAdd, VscEdit, VscTrash } from "react-icons/vsc";
 
@@ -11,6 +12,7 @@ import { CreateEditMtgModal, MtgTable, SelectedMtgPanel } from "../components/ma
 
 import { CardEntry as PokemonCardEntry } from "../types/pokemon";
 import { CardEntry as MagicCardEntry } from "../types/magic";
+import { BulkImportReport, DeduplicationReport, IdentityReport, ImageCheckReport, ImageRenameReport, JobOutcome, SetUpdateReport } from "../types";
 
 
 function App() {
@@ -19,13 +21,20 @@ function App() {
   const [selectedEntry, setSelectedEntry] = useState<PokemonCardEntry | MagicCardEntry>(null);
 
   const [activeGame, setActiveGame] = useState<string>(null);
+  // incremented whenever the backend changed the collection, e.g. by linking card identities
+  const [collectionVersion, setCollectionVersion] = useState<number>(0);
 
   const [createEditMode, setCreateEditMode] = useState<Mode>(Mode.Create);
 
   const [settingsModalVisible, setSettingsModalVisible] = useState<boolean>(false);
   const [createEditModalVisible, setCreateEditModalVisible] = useState<boolean>(false);
   const [deleteConfirmModalVisible, setDeleteConfirmModalVisible] = useState<boolean>(false);
+  const [imageRepairModalVisible, setImageRepairModalVisible] = useState<boolean>(false);
+  const [imageCheckReport, setImageCheckReport] = useState<ImageCheckReport>(null);
+  const [imageCheckGame, setImageCheckGame] = useState<string>(null);
   const [notificationModalVisible, setNotificationModalVisible] = useState<boolean>(false);
+  const [notificationTitle, setNotificationTitle] = useState<string>(null);
+  const [notificationText, setNotificationText] = useState<string>(null);
 
   const [imageModalVisible, setImageModalVisible] = useState<boolean>(false);
   const [imageModalImageIndex, setImageModalImageIndex] = useState<number>(0);
@@ -56,19 +65,187 @@ function App() {
         setSelectedEntry(null);
       }
       if (event.payload == "update/sets/pokemon") {
-        invoke("update_sets", {game: "Pokemon"})
-        .then(() => setNotificationModalVisible(true));
+        invoke("update_sets", {game: "Pokemon"});
       }
       if (event.payload == "update/sets/magic") {
-        invoke("update_sets", {game: "Magic"})
-        .then(() => setNotificationModalVisible(true));
+        invoke("update_sets", {game: "Magic"});
+      }
+      if (event.payload == "update/bulk/magic") {
+        invoke("import_magic_bulk_data", {path: null});
+      }
+      if (event.payload == "update/bulk/magic/file") {
+        open({
+          multiple: false,
+          title: "Select Scryfall bulk data file",
+          filters: [{ name: "JSON", extensions: ["json"] }],
+        }).then((selected) => {
+          if (selected && !Array.isArray(selected)) {
+            invoke("import_magic_bulk_data", {path: selected});
+          }
+        });
+      }
+      if (event.payload == "resolve_identities/pokemon") {
+        invoke("resolve_identities", {game: "Pokemon"});
+      }
+      if (event.payload == "resolve_identities/magic") {
+        invoke("resolve_identities", {game: "Magic"});
+      }
+      if (event.payload == "rename_images/pokemon") {
+        invoke("rename_images", {game: "Pokemon"});
+      }
+      if (event.payload == "rename_images/magic") {
+        invoke("rename_images", {game: "Magic"});
+      }
+      if (event.payload == "deduplicate_images/pokemon") {
+        invoke("deduplicate_images", {game: "Pokemon"});
+      }
+      if (event.payload == "deduplicate_images/magic") {
+        invoke("deduplicate_images", {game: "Magic"});
+      }
+      if (event.payload == "check_images/pokemon") {
+        checkImages("Pokemon");
+      }
+      if (event.payload == "check_images/magic") {
+        checkImages("Magic");
+      }
+    });
+
+    // set updates and identity resolutions run as background jobs, show their report once they are finished
+    listen("job://finished", (event) => {
+      const outcome = event.payload as JobOutcome;
+      if (outcome.kind == "update_sets" && outcome.result) {
+        showSetUpdateReport(JSON.parse(outcome.result));
+      }
+      if (outcome.kind == "update_sets" && outcome.error && !outcome.cancelled) {
+        setNotificationTitle("Sets Update");
+        setNotificationText(`Sets could not be updated: ${outcome.error}`);
+        setNotificationModalVisible(true);
+      }
+      if (outcome.kind == "import_magic_bulk_data" && outcome.result) {
+        showBulkImportReport(JSON.parse(outcome.result));
+      }
+      if (outcome.kind == "resolve_identities" && outcome.result) {
+        setCollectionVersion((version) => version + 1);
+        showIdentityReport(JSON.parse(outcome.result));
+      }
+      if (outcome.kind == "rename_images" && outcome.result) {
+        // the selected entry still refers to the previous image names
+        setSelectedEntry(null);
+        setCollectionVersion((version) => version + 1);
+        showImageRenameReport(JSON.parse(outcome.result));
+      }
+      if (outcome.kind == "deduplicate_images" && outcome.result) {
+        // the selected entry may still refer to merged images
+        setSelectedEntry(null);
+        setCollectionVersion((version) => version + 1);
+        showDeduplicationReport(JSON.parse(outcome.result));
       }
     });
   }, []);
 
+  const showSetUpdateReport = (report: SetUpdateReport) => {
+    setNotificationTitle("Sets Update");
+    setNotificationText(
+      `Sets were updated successfully: ${report.added.length} new, ${report.renamed.length} renamed, ` +
+      `${report.releaseDateChanged.length} with changed release date, ${report.removed.length} removed. ` +
+      `${report.affectedEntries.length} collection entries reference changed or removed sets.`
+    );
+    setNotificationModalVisible(true);
+  };
+
+  const showBulkImportReport = (report: BulkImportReport) => {
+    setNotificationTitle("Magic Card Data");
+    setNotificationText(report.updated
+      ? `${report.cards} cards of ${report.sets} sets were imported successfully.`
+      : `Card data is already up-to-date (${report.cards} cards of ${report.sets} sets).`
+    );
+    setNotificationModalVisible(true);
+  };
+
+  const showImageRenameReport = (report: ImageRenameReport) => {
+    setNotificationTitle("Rename Images");
+    setNotificationText(
+      `${report.renamed} images were renamed, ${report.unchanged} already matched the naming scheme` +
+      (report.failed.length > 0 ? `. ${report.failed.length} images could not be renamed: ${report.failed.join(", ")}` : ".")
+    );
+    setNotificationModalVisible(true);
+  };
+
+  const showDeduplicationReport = (report: DeduplicationReport) => {
+    const formatSize = (bytes: number) => `${(bytes / (1024 * 1024)).toFixed(1)} MB`;
+    setNotificationTitle("Deduplicate Images");
+    setNotificationText(
+      `${report.merged} duplicate images were merged. ${report.images} images with ${formatSize(report.storedBytes)} ` +
+      `are used by ${report.references} references, sharing identical images saves ${formatSize(report.savedBytes)}.`
+    );
+    setNotificationModalVisible(true);
+  };
+
+  const checkImages = (game: string) => {
+    invoke("check_images", {game: game}).then((result) => {
+      const report: ImageCheckReport = JSON.parse(result as string);
+      if (report.orphans.length == 0 && report.dangling.length == 0) {
+        setNotificationTitle("Check Images");
+        setNotificationText("All images are referenced by collection entries and all referenced images exist.");
+        setNotificationModalVisible(true);
+        return;
+      }
+      setImageCheckGame(game);
+      setImageCheckReport(report);
+      setImageRepairModalVisible(true);
+    });
+  };
+
+  const getImageCheckText = (report: ImageCheckReport) => {
+    if (!report) {
+      return "";
+    }
+    const attachable = report.orphans.filter((orphan) => orphan.suggestedEntryId != null);
+    const unknown = report.orphans.filter((orphan) => orphan.suggestedEntryId == null);
+    return (
+      `${attachable.length} orphaned images can be attached to the entries their names suggest` +
+      (attachable.length > 0 ? ` (${attachable.map((orphan) => `${orphan.image} to #${orphan.suggestedEntryId}`).join(", ")})` : "") +
+      `, ${unknown.length} orphaned images do not belong to any entry` +
+      (unknown.length > 0 ? ` (${unknown.map((orphan) => orphan.image).join(", ")})` : "") +
+      ` and ${report.dangling.length} references to missing images were found` +
+      (report.dangling.length > 0 ? ` (${report.dangling.map((dangling) => `${dangling.image} of #${dangling.entryId}`).join(", ")})` : "") +
+      `. Do you want to attach the suggested images, delete the others and remove the missing references?`
+    );
+  };
+
+  const repairImages = () => {
+    const attach = imageCheckReport.orphans.filter((orphan) => orphan.suggestedEntryId != null).map((orphan) => orphan.image);
+    const remove = imageCheckReport.orphans.filter((orphan) => orphan.suggestedEntryId == null).map((orphan) => orphan.image);
+    invoke("repair_images", {attach: attach, delete: remove, removeDangling: true, game: imageCheckGame}).then((result) => {
+      const report: ImageCheckReport = JSON.parse(result as string);
+      // the selected entry may still refer to removed images
+      setSelectedEntry(null);
+      setCollectionVersion((version) => version + 1);
+      setNotificationTitle("Check Images");
+      setNotificationText(report.orphans.length == 0 && report.dangling.length == 0
+        ? "Images were repaired successfully."
+        : `${report.orphans.length} orphaned images and ${report.dangling.length} references to missing images could not be repaired.`
+      );
+      setNotificationModalVisible(true);
+    });
+  };
+
+  const showIdentityReport = (report: IdentityReport) => {
+    const unresolved = report.ambiguous.concat(report.unmatched);
+    setNotificationTitle("Link Cards");
+    setNotificationText(
+      `${report.resolved} entries were linked, ${report.alreadyLinked} were already linked. ` +
+      `${report.ambiguous.length} entries are ambiguous and ${report.unmatched.length} could not be matched` +
+      (unresolved.length > 0
+        ? `: ${unresolved.map((entry) => `${entry.name} (${entry.setId.toUpperCase()} ${entry.setNo}) - ${entry.reason}`).join("; ")}`
+        : ".")
+    );
+    setNotificationModalVisible(true);
+  };
+
   /**
-   * Everytime the active game changes, fetch the corresponding collection
-   * of the active game from the backend.
+   * Everytime the active game changes or the backend changed the collection,
+   * fetch the corresponding collection of the active game from the backend.
    */
   useEffect(() => {
     if (activeGame) {
@@ -80,7 +257,7 @@ function App() {
           setCollection(Object.values(obj) as MagicCardEntry[]);
       });
     }
-  }, [activeGame]);
+  }, [activeGame, collectionVersion]);
 
   
   const deleteSelectedCard = () => {
@@ -233,11 +410,18 @@ function App() {
         title="Delete Entry"
         text="Do you really want to delete this entry?"
       />
+      <ConfirmationModal
+        visible={imageRepairModalVisible}
+        setVisible={setImageRepairModalVisible}
+        confirmAction={repairImages}
+        title="Check Images"
+        text={getImageCheckText(imageCheckReport)}
+      />
       <NotificationModal
         visible={notificationModalVisible}
         setVisible={setNotificationModalVisible}
-        title="Sets Update"
-        text="Sets were updated successfully."
+        title={notificationTitle}
+        text={notificationText}
       />
     </div>
   );
@@ -1,6 +1,38 @@
 export type Configuration = {
     dataStorage: string;
     defaultGame: string;
+    setMaxAgeHours?: number;
+    magic?: {
+        apiUrl: string;
+        setTypes: string[];
+        digitalSets: boolean;
+    };
+    pokemon?: {
+        apiUrl: string;
+        apiKey?: string;
+    };
+    http?: {
+        timeoutSecs: number;
+        retries: number;
+        retryBackoffMs: number;
+        requestIntervalMs: number;
+        proxy?: string;
+        userAgent?: string;
+    };
+    images?: ImageConfiguration;
+}
+
+/**
+ * Configuration of the collection images, see the `images` section of the configuration.
+ */
+export type ImageConfiguration = {
+    thumbnailSizes: number[];
+    filenameTemplate: string;
+    normalize: boolean;
+    maxSize: number;
+    format: "jpeg" | "png" | "webp";
+    quality: number;
+    keepOriginals: boolean;
 }
 
 /**
@@ -11,6 +43,7 @@ export type SetTemplate = {
     id: string;
     name: string;
     releaseDate: string;
+    custom?: boolean;
 }
 
 /**
@@ -25,8 +58,128 @@ export type EntryTemplate = {
     condition: string;
     set: SetTemplate;
     setNo?: string;
+    upstreamId?: string | null;
     images: string[];
+    imageRoles?: { [image: string]: string };
+    primaryImage?: string;
     note: string;
     signed: boolean;
     altered: boolean;
+}
+
+/**
+ * Differences between the stored sets of a game before and after a set update.
+ */
+export type SetUpdateReport = {
+    added: { id: string; name: string; releaseDate: string }[];
+    renamed: { id: string; name: string; previous: string; current: string }[];
+    releaseDateChanged: { id: string; name: string; previous: string; current: string }[];
+    removed: { id: string; name: string; releaseDate: string }[];
+    affectedEntries: { id: number; name: string; setId: string }[];
+}
+
+/**
+ * Result of linking the collection entries of a game to their card identities.
+ */
+export type IdentityReport = {
+    resolved: number;
+    alreadyLinked: number;
+    ambiguous: UnresolvedEntry[];
+    unmatched: UnresolvedEntry[];
+}
+
+/**
+ * Collection entry that could not be linked to its card identity.
+ */
+export type UnresolvedEntry = {
+    id: number;
+    name: string;
+    setId: string;
+    setNo: string;
+    reason: string;
+    candidates: CatalogCard[];
+}
+
+/**
+ * Result of an import of the Scryfall bulk data via backend command `import_magic_bulk_data`.
+ */
+export type BulkImportReport = {
+    updated: boolean;
+    updatedAt: string;
+    source: string;
+    cards: number;
+    sets: number;
+}
+
+/**
+ * Result of applying the filename template to the images of a collection.
+ */
+export type ImageRenameReport = {
+    renamed: number;
+    unchanged: number;
+    failed: string[];
+}
+
+/**
+ * Storage used by the images of a collection and storage saved by sharing images of the same content.
+ */
+export type DeduplicationReport = {
+    images: number;
+    references: number;
+    merged: number;
+    storedBytes: number;
+    savedBytes: number;
+}
+
+/**
+ * Image within the image directory that no collection entry references.
+ */
+export type OrphanedImage = {
+    image: string;
+    suggestedEntryId?: number;
+}
+
+/**
+ * Image reference of a collection entry, whose image does not exist.
+ */
+export type DanglingImage = {
+    entryId: number;
+    image: string;
+}
+
+/**
+ * Result of checking the images of a collection against the image directory.
+ */
+export type ImageCheckReport = {
+    orphans: OrphanedImage[];
+    dangling: DanglingImage[];
+}
+
+/**
+ * Outcome of a background job, emitted by the backend via event `job://finished`.
+ */
+export type JobOutcome = {
+    id: number;
+    kind: string;
+    result?: string;
+    error?: string;
+    cancelled: boolean;
+}
+
+/**
+ * Card of the catalog of a set, as returned by the backend command `get_catalog`.
+ */
+export type CatalogCard = {
+    id: string;
+    name: string;
+    number: string;
+    rarity?: string;
+}
+
+/**
+ * Card of the catalog of a set that matches a search via backend command `search_catalog`
+ * or a lookup via backend command `lookup_card`.
+ */
+export type CatalogMatch = CatalogCard & {
+    setId: string;
 }
\ No newline at end of file
@@ -2,6 +2,13 @@ export type Set = {
     id: string;
     name: string;
     releaseDate: string;
+    setType?: string;
+    printedTotal?: number;
+    total?: number;
+    series?: string;
+    parentSet?: string;
+    symbolUrl?: string;
+    custom?: boolean;
 }
 
 export type CardEntry = {
@@ -10,6 +17,7 @@ export type CardEntry = {
     name: string;
     set: Set;
     setNo: string;
+    upstreamId?: string | null;
     language: string;
     condition: string;
     amount: number;